@@ -0,0 +1,62 @@
+// Times the common array operations on a 100k-element tape, like the README's Turing machine
+// but long enough for a copy of the whole tape per operation to show. Run with
+// `cargo bench --bench tape`.
+
+use asa::*;
+use std::time::Instant;
+
+const TAPE: usize = 100_000;
+
+const SCRIPT: &str = r#"
+fn fill(n) {
+    let tape = [];
+    let i = 0;
+    while (i < n) {
+        tape.push(0);
+        i = i + 1;
+    }
+    return tape;
+}
+
+fn walk(tape) {
+    let pos = 0;
+    let n = tape.length;
+    while (pos < n) {
+        if (tape[pos] == 0) {
+            tape[pos] = 1;
+        }
+        pos = pos + 1;
+    }
+    return tape;
+}
+
+fn head(tape) {
+    return tape[0];
+}
+
+fn pass(tape, times) {
+    let i = 0;
+    while (i < times) {
+        head(tape);
+        i = i + 1;
+    }
+    return tape.length;
+}
+"#;
+
+fn time(name: &str, engine: &mut Engine, source: &str) {
+  let start = Instant::now();
+  let result = engine.eval(source).unwrap_or_else(|err| panic!("{} failed: {:?}", name, err));
+  println!("{:<40} {:>8.1} ms  -> {}", name, start.elapsed().as_secs_f64() * 1000.0, result);
+}
+
+fn main() {
+  let mut engine = Engine::new();
+  engine.set_array_semantics(ArraySemantics::Value);
+  engine.load(SCRIPT).unwrap();
+  time(&format!("fill a {}-element tape with push", TAPE), &mut engine, &format!("let tape = fill({}); tape.length", TAPE));
+  time("read and write every cell", &mut engine, "let walked = walk(tape); walked.length");
+  time("pass the tape to a function 100k times", &mut engine, &format!("pass(tape, {})", TAPE));
+  // The original is untouched by walk, which had to copy it once
+  time("check value semantics held", &mut engine, "[tape[0], walked[0]]");
+}
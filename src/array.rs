@@ -0,0 +1,172 @@
+// Arrays are held through a shared handle, so passing one around never copies its elements.
+// Whether a change made through one handle shows through the others is up to the interpreter's
+// ArraySemantics, which maps follow as well.
+
+use crate::interpreter::{grow_stack, Interpreter, Value};
+use std::cell::{Ref, RefCell, RefMut};
+use std::collections::HashSet;
+use std::fmt;
+use std::rc::Rc;
+
+// What assigning an array or map, or passing it to a function, gives the receiving variable
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ArraySemantics {
+  // The same one: `fn grow(tape) { tape.push(0); }` changes the caller's tape too
+  #[default]
+  Reference,
+  // Its own copy: changes made through one variable are never seen through another
+  Value,
+}
+
+#[derive(Clone, Default)]
+pub struct Array(Rc<RefCell<Vec<Value>>>);
+
+impl Array {
+  pub fn new(items: Vec<Value>) -> Array {
+    Array(Rc::new(RefCell::new(items)))
+  }
+
+  pub fn borrow(&self) -> Ref<'_, Vec<Value>> {
+    self.0.borrow()
+  }
+
+  pub fn borrow_mut(&self) -> RefMut<'_, Vec<Value>> {
+    self.0.borrow_mut()
+  }
+
+  pub fn len(&self) -> usize {
+    self.0.borrow().len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.0.borrow().is_empty()
+  }
+
+  pub fn get(&self, idx: usize) -> Option<Value> {
+    self.0.borrow().get(idx).cloned()
+  }
+
+  pub fn to_vec(&self) -> Vec<Value> {
+    self.0.borrow().clone()
+  }
+
+  // Whether both handles refer to the same array, rather than to equal ones
+  pub fn ptr_eq(&self, other: &Array) -> bool {
+    Rc::ptr_eq(&self.0, &other.0)
+  }
+
+  // Identifies the array while it's alive, e.g. to notice it again inside itself
+  pub(crate) fn addr(&self) -> usize {
+    Rc::as_ptr(&self.0) as usize
+  }
+
+  // Empties the array if this is the last handle to it, returning what it held
+  pub(crate) fn take_if_last(&self) -> Option<Vec<Value>> {
+    (Rc::strong_count(&self.0) == 1).then(|| std::mem::take(&mut *self.0.borrow_mut()))
+  }
+
+  // A new array holding the same elements. Nested arrays are still shared.
+  pub fn snapshot(&self) -> Array {
+    Array::new(self.to_vec())
+  }
+
+  // Gives this handle an array of its own if any other handle shares its current one, so
+  // changing it can't be seen through them
+  pub fn make_unique(&mut self) {
+    if Rc::strong_count(&self.0) > 1 {
+      *self = self.snapshot();
+    }
+  }
+
+  // Formats the elements, writing `[...]` instead for an array that contains itself
+  pub fn fmt_items(&self, f: &mut fmt::Formatter, item: impl Fn(&Value, &mut fmt::Formatter) -> fmt::Result) -> fmt::Result {
+    format_once(self.addr(), f, "[...]", |f| {
+      write!(f, "[")?;
+      for (i, value) in self.borrow().iter().enumerate() {
+        if i > 0 {
+          write!(f, ", ")?;
+        }
+        item(value, f)?;
+      }
+      write!(f, "]")
+    })
+  }
+}
+
+// Formats a collection, writing `placeholder` instead if it's already being formatted further
+// out, so one that contains itself (only possible with reference semantics) doesn't recurse
+// forever. `ptr` identifies the collection.
+pub(crate) fn format_once(ptr: usize, f: &mut fmt::Formatter, placeholder: &str, body: impl FnOnce(&mut fmt::Formatter) -> fmt::Result) -> fmt::Result {
+  if !FORMATTING.with(|open| open.borrow_mut().insert(ptr)) {
+    return write!(f, "{}", placeholder);
+  }
+  let result = grow_stack(|| body(f));
+  FORMATTING.with(|open| open.borrow_mut().remove(&ptr));
+  result
+}
+
+// Compares two collections with `body`, unless the same pair is already being compared further
+// out. Two collections that contain themselves (only possible with reference semantics) would
+// otherwise be compared forever; finding the pair again means nothing along the way differed, so
+// it counts as equal. `pair` identifies the two collections.
+pub(crate) fn compare_once(pair: (usize, usize), body: impl FnOnce() -> bool) -> bool {
+  if !COMPARING.with(|open| open.borrow_mut().insert(pair)) {
+    return true;
+  }
+  let result = grow_stack(body);
+  COMPARING.with(|open| open.borrow_mut().remove(&pair));
+  result
+}
+
+thread_local! {
+  // The collections currently being formatted
+  static FORMATTING: RefCell<HashSet<usize>> = RefCell::new(HashSet::new());
+  // The pairs of collections currently being compared
+  static COMPARING: RefCell<HashSet<(usize, usize)>> = RefCell::new(HashSet::new());
+}
+
+// Dropping the last handle to a collection drops its elements, which would recurse once per level
+// of nesting and overflow the native stack on deeply nested values. Instead the contents of each
+// collection nothing else holds are moved onto a list first, so every level is dropped empty.
+pub(crate) fn drop_contents(mut pending: Vec<Value>) {
+  while let Some(value) = pending.pop() {
+    let held = match &value {
+      Value::Array(arr) => arr.take_if_last(),
+      Value::Map(map) => map.take_if_last(),
+      _ => None,
+    };
+    pending.extend(held.into_iter().flatten());
+  }
+}
+
+impl Drop for Array {
+  fn drop(&mut self) {
+    if let Some(items) = self.take_if_last() {
+      drop_contents(items);
+    }
+  }
+}
+
+impl fmt::Debug for Array {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    self.fmt_items(f, |value, f| write!(f, "{:?}", value))
+  }
+}
+
+// `==` compares arrays by their elements, as `==` compares values, so `[1] == [1.0]`; `same(a, b)`
+// is the identity check
+impl PartialEq for Array {
+  fn eq(&self, other: &Array) -> bool {
+    compare_once((self.addr(), other.addr()), || {
+      let (items, other_items) = (self.borrow(), other.borrow());
+      items.len() == other_items.len() &&
+        items.iter().zip(other_items.iter()).all(|(a, b)| Interpreter::values_equal(a, b))
+    })
+  }
+}
+
+impl From<Vec<Value>> for Array {
+  fn from(items: Vec<Value>) -> Array {
+    Array::new(items)
+  }
+}
@@ -0,0 +1,195 @@
+// Functions implemented in Rust and callable from asa. The interpreter starts out with the
+// standard ones below, and hosts can add their own with `Interpreter::register_builtin`.
+
+use std::fmt;
+
+use crate::error::*;
+use crate::interpreter::*;
+use crate::array::ArraySemantics;
+use num_bigint::BigInt;
+use num_traits::{FromPrimitive, ToPrimitive};
+use rust_decimal::{Decimal, RoundingStrategy};
+
+pub type NativeFn = dyn Fn(&mut Interpreter, &[Value]) -> Result<Value, AsaErrorKind>;
+
+// How many arguments a builtin accepts
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Arity {
+  Exact(usize),
+  Range(usize, usize),
+  AtLeast(usize),
+}
+
+impl Arity {
+  pub fn accepts(&self, count: usize) -> bool {
+    match *self {
+      Arity::Exact(n) => count == n,
+      Arity::Range(min, max) => count >= min && count <= max,
+      Arity::AtLeast(min) => count >= min,
+    }
+  }
+}
+
+impl fmt::Display for Arity {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Arity::Exact(n) => write!(f, "{}", n),
+      Arity::Range(min, max) => write!(f, "{}..{}", min, max),
+      Arity::AtLeast(min) => write!(f, "{}..", min),
+    }
+  }
+}
+
+pub struct NativeFunction {
+  pub name: String,
+  pub arity: Arity,
+  pub func: Box<NativeFn>,
+}
+
+impl NativeFunction {
+  pub fn new<F>(name: &str, arity: Arity, func: F) -> NativeFunction
+    where F: Fn(&mut Interpreter, &[Value]) -> Result<Value, AsaErrorKind> + 'static {
+    NativeFunction { name: name.to_string(), arity, func: Box::new(func) }
+  }
+
+  // Checks the argument count, then runs the function
+  pub fn call(&self, interpreter: &mut Interpreter, args: &[Value]) -> Result<Value, AsaErrorKind> {
+    if !self.arity.accepts(args.len()) {
+      let noun = if self.arity == Arity::Exact(1) { "argument" } else { "arguments" };
+      return Err(AsaErrorKind::WrongArgumentCount(format!(
+        "{} expects {} {}, got {}", self.name, self.arity, noun, args.len()
+      )));
+    }
+    (self.func)(interpreter, args)
+  }
+}
+
+impl fmt::Debug for NativeFunction {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "NativeFunction({}/{})", self.name, self.arity)
+  }
+}
+
+// Native functions are identified by name, the closure itself can't be compared
+impl PartialEq for NativeFunction {
+  fn eq(&self, other: &NativeFunction) -> bool {
+    self.name == other.name && self.arity == other.arity
+  }
+}
+
+// The builtins every interpreter starts with
+pub fn register_defaults(interpreter: &mut Interpreter) {
+  // print(x) writes to the stdout sink, eprint(x) to the stderr sink
+  interpreter.register_builtin("print", Arity::Exact(1), |interp, args| {
+    writeln!(interp.stdout(), "{}", args[0])
+      .map_err(|e| AsaErrorKind::Generic(format!("Could not write output: {}", e)))?;
+    Ok(Value::Bool(true))
+  });
+  interpreter.register_builtin("eprint", Arity::Exact(1), |interp, args| {
+    writeln!(interp.stderr(), "{}", args[0])
+      .map_err(|e| AsaErrorKind::Generic(format!("Could not write output: {}", e)))?;
+    Ok(Value::Bool(true))
+  });
+
+  interpreter.register_builtin("len", Arity::Exact(1), |_, args| {
+    match &args[0] {
+      Value::String(s) => Ok(Value::Number(s.chars().count() as i32)),
+      Value::Array(arr) => Ok(Value::Number(arr.len() as i32)),
+      Value::Map(map) => Ok(Value::Number(map.len() as i32)),
+      other => Err(AsaErrorKind::TypeMismatch(format!("len() not supported on {}", other.type_name()))),
+    }
+  });
+
+  // copy(x): a copy of x that later changes to x can't reach, nested arrays and maps included
+  interpreter.register_builtin("copy", Arity::Exact(1), |interp, args| {
+    let copy = args[0].deep_copy();
+    interp.charge_bytes(Interpreter::memory_size(&copy))?;
+    Ok(copy)
+  });
+
+  // same(a, b): whether a and b are the same array, map or object, not just equal ones. Under
+  // value semantics arrays and maps have no identity, so equal ones are the same.
+  interpreter.register_builtin("same", Arity::Exact(2), |interp, args| {
+    let same = match (&args[0], &args[1]) {
+      (Value::Array(a), Value::Array(b)) if interp.array_semantics() == ArraySemantics::Reference => a.ptr_eq(b),
+      (Value::Map(a), Value::Map(b)) if interp.array_semantics() == ArraySemantics::Reference => a.ptr_eq(b),
+      (a, b) => Interpreter::values_equal(a, b),
+    };
+    Ok(Value::Bool(same))
+  });
+
+  // int(x), truncating floats toward zero
+  interpreter.register_builtin("int", Arity::Exact(1), |_, args| {
+    match &args[0] {
+      Value::Number(n) => Ok(Value::Number(*n)),
+      Value::BigInt(n) => Ok(Value::BigInt(n.clone())),
+      Value::Decimal(d) => Ok(Interpreter::normalize_int(d.trunc().to_string().parse::<BigInt>().unwrap())),
+      Value::Float(f) => {
+        match BigInt::from_f64(f.trunc()) {
+          Some(n) => Ok(Interpreter::normalize_int(n)),
+          None => Err(AsaErrorKind::NumberOverflow),
+        }
+      }
+      Value::String(s) => s.trim().parse::<BigInt>()
+        .map(Interpreter::normalize_int)
+        .map_err(|_| AsaErrorKind::TypeMismatch(format!("Cannot convert \"{}\" to int", s))),
+      other => Err(AsaErrorKind::TypeMismatch(format!("int() not supported on {}", other.type_name()))),
+    }
+  });
+
+  // float(x)
+  interpreter.register_builtin("float", Arity::Exact(1), |_, args| {
+    match &args[0] {
+      Value::Number(n) => Ok(Value::Float(*n as f64)),
+      Value::BigInt(n) => Ok(Value::Float(n.to_f64().unwrap_or(f64::NAN))),
+      Value::Float(f) => Ok(Value::Float(*f)),
+      Value::Decimal(d) => Ok(Value::Float(d.to_f64().unwrap_or(f64::NAN))),
+      Value::String(s) => s.trim().parse::<f64>()
+        .map(Value::Float)
+        .map_err(|_| AsaErrorKind::TypeMismatch(format!("Cannot convert \"{}\" to float", s))),
+      other => Err(AsaErrorKind::TypeMismatch(format!("float() not supported on {}", other.type_name()))),
+    }
+  });
+
+  // decimal(x), from a string like "12.50" or from another number
+  interpreter.register_builtin("decimal", Arity::Exact(1), |_, args| {
+    match &args[0] {
+      Value::String(s) => Decimal::from_str_exact(s.trim())
+        .map(Value::Decimal)
+        .map_err(|_| AsaErrorKind::TypeMismatch(format!("Cannot convert \"{}\" to decimal", s))),
+      Value::Float(f) => Decimal::from_f64(*f)
+        .map(Value::Decimal)
+        .ok_or_else(|| AsaErrorKind::TypeMismatch(format!("Cannot convert {} to decimal", f))),
+      other => Ok(Value::Decimal(Interpreter::as_decimal(other)?)),
+    }
+  });
+
+  // round(d, scale, mode = "half_even") and div(a, b, scale, mode = "half_even")
+  interpreter.register_builtin("round", Arity::Range(2, 3), |_, args| round_decimal(&args[0], None, &args[1..]));
+  interpreter.register_builtin("div", Arity::Range(3, 4), |_, args| round_decimal(&args[0], Some(&args[1]), &args[2..]));
+}
+
+// Shared by round and div: `options` holds the scale and the optional rounding mode
+fn round_decimal(value: &Value, divisor: Option<&Value>, options: &[Value]) -> Result<Value, AsaErrorKind> {
+  let scale = match options[0] {
+    Value::Number(n) if n >= 0 => n as u32,
+    _ => return Err(AsaErrorKind::TypeMismatch("Scale must be a non-negative number".to_string())),
+  };
+  let strategy = match options.get(1) {
+    Some(Value::String(mode)) => Interpreter::rounding_strategy(mode)?,
+    Some(_) => return Err(AsaErrorKind::TypeMismatch("Rounding mode must be a string".to_string())),
+    None => RoundingStrategy::MidpointNearestEven,
+  };
+  let mut amount = Interpreter::as_decimal(value)?;
+  if let Some(divisor) = divisor {
+    let divisor = Interpreter::as_decimal(divisor)?;
+    if divisor.is_zero() {
+      return Err(AsaErrorKind::DivisionByZero);
+    }
+    amount = amount.checked_div(divisor).ok_or(AsaErrorKind::NumberOverflow)?;
+  }
+  let mut rounded = amount.round_dp_with_strategy(scale, strategy);
+  // Pad to the requested scale so `round(2d, 2)` formats as 2.00
+  rounded.rescale(scale);
+  Ok(Value::Decimal(rounded))
+}
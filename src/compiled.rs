@@ -0,0 +1,71 @@
+// A program lexed, parsed and checked once, then run any number of times. It holds no runtime
+// state, so one CompiledProgram can be shared between threads in an Arc, and every interpreter
+// instantiated from it gets its own globals while sharing the function definitions.
+
+use std::sync::Arc;
+
+use crate::error::*;
+use crate::interpreter::*;
+use crate::parser::*;
+use crate::validate::validate;
+
+#[derive(Debug)]
+enum Item {
+  // A top-level function, prepared ahead of time so instantiating doesn't copy its body
+  Define(u64, Arc<FunctionDef>),
+  Run(Node),
+}
+
+#[derive(Debug)]
+pub struct CompiledProgram {
+  items: Vec<Item>,
+}
+
+impl CompiledProgram {
+  pub fn compile(source: &str) -> Result<CompiledProgram, AsaError> {
+    let tree = parse_source(source)?;
+    validate(&tree)?;
+    let children = match tree {
+      Node::Program { children } => children,
+      other => vec![other],
+    };
+    let mut items = Vec::new();
+    for node in children {
+      match node {
+        Node::FunctionDefine { name, children } => {
+          let def = Interpreter::function_def(&name, &children)?;
+          items.push(Item::Define(Interpreter::hash_identifier(&name), Arc::new(def)));
+        }
+        other => items.push(Item::Run(other)),
+      }
+    }
+    Ok(CompiledProgram { items })
+  }
+
+  // A fresh interpreter with the program's top level already run
+  pub fn instantiate(&self) -> Result<Interpreter, AsaError> {
+    let mut interpreter = Interpreter::new();
+    self.run(&mut interpreter)?;
+    Ok(interpreter)
+  }
+
+  // Runs the program's top level in an existing interpreter, returning its last value
+  pub fn run(&self, interpreter: &mut Interpreter) -> Result<Value, AsaError> {
+    let mut last = Value::Bool(true);
+    for item in &self.items {
+      match item {
+        Item::Define(id, def) => {
+          interpreter.set_global(*id, Value::Function(def.clone()));
+          last = Value::Bool(true);
+        }
+        Item::Run(node) => match interpreter.exec_flow(node)? {
+          ControlFlow::Next(val) => last = val,
+          ControlFlow::Return(val) => return Ok(val),
+          ControlFlow::Break => return Err(AsaErrorKind::BreakOutsideLoop.into()),
+          ControlFlow::Continue => return Err(AsaErrorKind::ContinueOutsideLoop.into()),
+        },
+      }
+    }
+    Ok(last)
+  }
+}
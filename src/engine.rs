@@ -0,0 +1,75 @@
+// A high-level entry point for running asa from Rust: source strings in, values out, without
+// touching the lexer, parser or identifier hashes.
+
+use crate::builtins::Arity;
+use crate::compiled::CompiledProgram;
+use crate::error::*;
+use crate::interpreter::*;
+use crate::limits::Limits;
+use crate::array::ArraySemantics;
+use crate::parser::parse_source;
+use crate::validate::validate;
+
+#[derive(Debug, Default)]
+pub struct Engine {
+  interpreter: Interpreter,
+}
+
+impl Engine {
+  pub fn new() -> Engine {
+    Engine::default()
+  }
+
+  // An engine whose globals start out as those defined by a compiled program
+  pub fn from_program(program: &CompiledProgram) -> Result<Engine, AsaError> {
+    Ok(Engine { interpreter: program.instantiate()? })
+  }
+
+  // Runs a script and returns the value of its last statement. Definitions stay around for
+  // later calls to eval, call and get_global.
+  pub fn eval(&mut self, source: &str) -> Result<Value, AsaError> {
+    let tree = parse_source(source)?;
+    validate(&tree)?;
+    self.interpreter.eval(&tree)
+  }
+
+  // Runs a script for its definitions, discarding its value
+  pub fn load(&mut self, source: &str) -> Result<(), AsaError> {
+    self.eval(source).map(|_| ())
+  }
+
+  // Calls a function defined by a script, or set with set_global, by name
+  pub fn call(&mut self, name: &str, args: &[Value]) -> Result<Value, AsaError> {
+    let func = self.get_global(name).ok_or(AsaErrorKind::UndefinedFunction)?;
+    self.interpreter.call_value(func, args.to_vec())
+  }
+
+  // Makes a Rust function callable from scripts, see `Interpreter::register_builtin`
+  pub fn register_fn<F>(&mut self, name: &str, arity: Arity, func: F)
+    where F: Fn(&mut Interpreter, &[Value]) -> Result<Value, AsaErrorKind> + 'static {
+    self.interpreter.register_builtin(name, arity, func);
+  }
+
+  // Limits for everything the engine runs from now on, see `Interpreter::set_limits`
+  pub fn set_limits(&mut self, limits: Limits) {
+    self.interpreter.set_limits(limits);
+  }
+
+  // See `Interpreter::set_array_semantics`
+  pub fn set_array_semantics(&mut self, semantics: ArraySemantics) {
+    self.interpreter.set_array_semantics(semantics);
+  }
+
+  pub fn set_global(&mut self, name: &str, value: Value) {
+    self.interpreter.set_global(Interpreter::hash_identifier(name.as_bytes()), value);
+  }
+
+  pub fn get_global(&self, name: &str) -> Option<Value> {
+    self.interpreter.get_variable(Interpreter::hash_identifier(name.as_bytes())).ok()
+  }
+
+  // The underlying interpreter, e.g. to redirect or capture its output
+  pub fn interpreter(&mut self) -> &mut Interpreter {
+    &mut self.interpreter
+  }
+}
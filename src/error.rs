@@ -1,7 +1,9 @@
 
 // You are free to add more error variants if you need them.
 
-use crate::Value;
+use std::fmt;
+
+use crate::lexer::Span;
 
 #[derive(Debug,PartialEq)]
 pub enum AsaErrorKind {
@@ -12,7 +14,239 @@ pub enum AsaErrorKind {
   NumberUnderflow,
   TypeMismatch(String),
   Generic(String),
-  BreakSignal,
-  ContinueSignal,
-  ReturnSignal(Value),
-}
\ No newline at end of file
+  BreakOutsideLoop,
+  ContinueOutsideLoop,
+  ReturnOutsideFunction,
+  // `place` names the array that was indexed, when the index was part of an assignment target
+  IndexOutOfRange { index: i32, len: usize, place: Option<String> },
+  NoMatchingArm(String),
+  KeyNotFound(String),
+  UnknownMethod { type_name: String, method: String },
+  WrongArgumentCount(String),
+  UnknownRoundingMode(String),
+  PopOnEmptyArray,
+  UnexpectedCharacter(char),
+  UnterminatedString,
+  SyntaxError(String),
+  OutOfFuel(u64),
+  CallDepthExceeded(usize),
+  MemoryLimitExceeded(usize),
+  DeadlineExceeded,
+}
+
+impl AsaErrorKind {
+  // Stable identifier for tooling and `asa explain`: E00xx lexer/parser, E01xx runtime, E02xx static checks
+  pub fn code(&self) -> &'static str {
+    match self {
+      AsaErrorKind::UnexpectedCharacter(_) => "E0001",
+      AsaErrorKind::UnterminatedString => "E0002",
+      AsaErrorKind::SyntaxError(_) => "E0003",
+      AsaErrorKind::Generic(_) => "E0100",
+      AsaErrorKind::IndexOutOfRange { .. } => "E0101",
+      AsaErrorKind::UndefinedFunction => "E0102",
+      AsaErrorKind::VariableNotDefined(_) => "E0103",
+      AsaErrorKind::TypeMismatch(_) => "E0104",
+      AsaErrorKind::DivisionByZero => "E0105",
+      AsaErrorKind::NumberOverflow => "E0106",
+      AsaErrorKind::NumberUnderflow => "E0107",
+      AsaErrorKind::NoMatchingArm(_) => "E0108",
+      AsaErrorKind::KeyNotFound(_) => "E0109",
+      AsaErrorKind::OutOfFuel(_) => "E0110",
+      AsaErrorKind::CallDepthExceeded(_) => "E0111",
+      AsaErrorKind::MemoryLimitExceeded(_) => "E0112",
+      AsaErrorKind::DeadlineExceeded => "E0113",
+      AsaErrorKind::UnknownMethod { .. } => "E0114",
+      AsaErrorKind::WrongArgumentCount(_) => "E0115",
+      AsaErrorKind::UnknownRoundingMode(_) => "E0116",
+      AsaErrorKind::PopOnEmptyArray => "E0117",
+      AsaErrorKind::BreakOutsideLoop => "E0201",
+      AsaErrorKind::ContinueOutsideLoop => "E0202",
+      AsaErrorKind::ReturnOutsideFunction => "E0203",
+    }
+  }
+}
+
+impl fmt::Display for AsaErrorKind {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      AsaErrorKind::UndefinedFunction => write!(f, "undefined function or variable"),
+      AsaErrorKind::VariableNotDefined(name) => write!(f, "variable `{}` is not defined", name),
+      AsaErrorKind::DivisionByZero => write!(f, "division by zero"),
+      AsaErrorKind::NumberOverflow => write!(f, "number overflow"),
+      AsaErrorKind::NumberUnderflow => write!(f, "number underflow"),
+      AsaErrorKind::TypeMismatch(msg) => write!(f, "type mismatch: {}", msg),
+      AsaErrorKind::Generic(msg) => write!(f, "{}", msg),
+      AsaErrorKind::BreakOutsideLoop => write!(f, "`break` outside of a loop"),
+      AsaErrorKind::ContinueOutsideLoop => write!(f, "`continue` outside of a loop"),
+      AsaErrorKind::ReturnOutsideFunction => write!(f, "`return` outside of a function"),
+      AsaErrorKind::IndexOutOfRange { index, len, place: None } => write!(f, "index {} out of range for length {}", index, len),
+      AsaErrorKind::IndexOutOfRange { index, len, place: Some(place) } => {
+        write!(f, "index {} out of range for `{}` of length {}", index, place, len)
+      }
+      AsaErrorKind::NoMatchingArm(value) => write!(f, "no match arm matches {}", value),
+      AsaErrorKind::KeyNotFound(key) => write!(f, "key {} not found in map", key),
+      AsaErrorKind::UnknownMethod { type_name, method } => write!(f, "{} has no method {}", type_name, method),
+      AsaErrorKind::WrongArgumentCount(msg) => write!(f, "{}", msg),
+      AsaErrorKind::UnknownRoundingMode(mode) => write!(f, "unknown rounding mode `{}`", mode),
+      AsaErrorKind::PopOnEmptyArray => write!(f, "pop on empty array"),
+      AsaErrorKind::UnexpectedCharacter(c) => write!(f, "unexpected character `{}`", c),
+      AsaErrorKind::UnterminatedString => write!(f, "unterminated string literal"),
+      AsaErrorKind::SyntaxError(msg) => write!(f, "syntax error: {}", msg),
+      AsaErrorKind::OutOfFuel(fuel) => write!(f, "ran out of fuel after {} steps", fuel),
+      AsaErrorKind::CallDepthExceeded(max) => write!(f, "call depth exceeded the limit of {}", max),
+      AsaErrorKind::MemoryLimitExceeded(max) => write!(f, "memory use exceeded the limit of {} bytes", max),
+      AsaErrorKind::DeadlineExceeded => write!(f, "execution passed its deadline"),
+    }
+  }
+}
+
+impl std::error::Error for AsaErrorKind {}
+
+// Longer description of an error code, shown by `asa explain <code>`
+pub fn explain(code: &str) -> Option<&'static str> {
+  let text = match code {
+    "E0001" => "The lexer found a character that is not part of asa's syntax, for example `$` or `@`.\n\
+      Remove the character, or put it inside a string literal.",
+    "E0002" => "A string literal was opened with `\"` but the file ended before the closing `\"`.\n\
+      Add the missing quote.",
+    "E0003" => "The parser could not make sense of the tokens at this location, for example an unbalanced\n\
+      bracket or a missing `;`. The reported span is the first token that could not be parsed.",
+    "E0100" => "A runtime error that has no more specific code, such as using `>` on booleans or a `step`\n\
+      of zero in a range. The message describes what went wrong.",
+    "E0101" => "An array or string was indexed with a position outside of it. Valid indices run from 0 to\n\
+      len() - 1, e.g.\n\n    let a = [1, 2];\n    a[2]; // error: index 2 out of range for length 2\n\n\
+      Check the index against len() before using it.",
+    "E0102" => "A function was called by a name that is not bound to any function or variable in scope.\n\
+      Check the spelling, and that the function is defined before the statement that calls it runs.",
+    "E0103" => "A variable was read that isn't defined in any scope visible here, e.g.\n\n    let y = x + 1; // error: variable `x` is not defined\n\n\
+      Check the spelling, and that it's defined with `let` before it's read. A function only sees its\n\
+      own variables, the ones its closure captured and the globals, not those of its caller.",
+    "E0104" => "An operator or builtin was applied to values of the wrong type, e.g. `1 + true`. Convert\n\
+      the values first with int(), float() or decimal().",
+    "E0105" => "The right-hand side of `/` or `%` was zero.",
+    "E0106" => "A number was too large to represent, for example a decimal outside of its 96-bit range, a\n\
+      float that can't be converted to an integer, or a power like `7 ^ 2000000000` whose result would\n\
+      have more than a million bits.",
+    "E0107" => "A number was too small to represent.",
+    "E0108" => "None of a `match` expression's arms matched the value, e.g.\n\n    match 3 { 1 | 2 => \"low\" } // error: no match arm matches 3\n\n\
+      Add an arm for the missing values, or end with `_ => ...` to catch everything else.",
+    "E0109" => "A map was read or indexed with a key it doesn't have, e.g.\n\n    let m = { a: 1 };\n    m.b; // error: key \"b\" not found in map\n\n\
+      Check for the key with `m.has(key)` first, or assign it before reading it.",
+    "E0110" => "The script used up the fuel the host gave it. Every evaluation step costs one unit of\n\
+      fuel, so this usually means a loop that doesn't end, or more work than the host allows.",
+    "E0111" => "More asa function calls were active at once than the host allows, usually because of\n\
+      recursion without a base case.",
+    "E0112" => "The script built more strings and arrays than the host allows. Memory is counted as values\n\
+      are created, so building a large string piece by piece with `+` counts every intermediate\n\
+      string. Changing an array or map in place, e.g. with `push`, only counts what it adds.",
+    "E0113" => "The script was still running when the deadline set by the host passed.",
+    "E0114" => "A method was called on a value whose type doesn't have it, e.g.\n\n    let x = 5;\n    x.push(1); // error: int has no method push\n\n\
+      Arrays have push, pop, insert, prepend, contains, join and copy; strings have upper, lower,\n\
+      trim, contains and split; maps have keys, values, has, remove and copy. Host objects list\n\
+      their own methods.",
+    "E0115" => "A function, builtin or method was called with the wrong number of arguments, e.g.\n\n    len(a, b); // error: len expects 1 argument, got 2\n\n\
+      A parameter without a default value must always be passed.",
+    "E0116" => "round() or div() was given a rounding mode it doesn't know. The modes are \"half_even\"\n\
+      (the default), \"half_up\", \"half_down\", \"up\", \"down\", \"ceiling\" and \"floor\".",
+    "E0117" => "pop() was called on an empty array. Check len() first, or that the array was filled.",
+    "E0201" => "`break` can only be used inside the body of a `while` or `for` loop. A `break` inside a function\n\
+      doesn't reach a loop in the function that called it.",
+    "E0202" => "`continue` can only be used inside the body of a `while` or `for` loop. A `continue` inside a\n\
+      function doesn't reach a loop in the function that called it.",
+    "E0203" => "`return` can only be used inside a function body, or inside an `if` or `match` that is\n\
+      used as a value.",
+    _ => return None,
+  };
+  Some(text)
+}
+
+// One asa function on the call stack when an error unwound through it
+#[derive(Debug, PartialEq, Clone)]
+pub struct TraceFrame {
+  pub function: String,
+  pub call_site: Option<Span>,
+}
+
+// An error together with where it happened: the span of the failing statement and the asa
+// functions it unwound through, innermost first
+#[derive(Debug, PartialEq)]
+pub struct AsaError {
+  pub kind: AsaErrorKind,
+  pub span: Option<Span>,
+  pub trace: Vec<TraceFrame>,
+}
+
+impl AsaError {
+  pub fn new(kind: AsaErrorKind) -> AsaError {
+    AsaError { kind, span: None, trace: vec![] }
+  }
+
+  // Records the location of the innermost failing node, keeping the first one recorded
+  pub fn at(mut self, span: Span) -> AsaError {
+    if self.span.is_none() {
+      self.span = Some(span);
+    }
+    self
+  }
+
+  pub fn render_traceback(&self, filename: &str) -> String {
+    let mut out = String::new();
+    if !self.trace.is_empty() {
+      out.push_str("Traceback (most recent call last):\n");
+      for note in self.notes(filename) {
+        out.push_str(&format!("  {}\n", note));
+      }
+    }
+    out.push_str(&self.to_string());
+    if let Some(span) = self.span {
+      out.push_str(&format!("\n  --> {}:{}:{}", filename, span.start_line, span.start_col));
+    }
+    out
+  }
+
+  // The call chain as one line per frame, outermost first
+  pub fn notes(&self, filename: &str) -> Vec<String> {
+    self.trace.iter().rev().map(|frame| match frame.call_site {
+      Some(site) => format!("in {}, called from {}:{}:{}", frame.function, filename, site.start_line, site.start_col),
+      None => format!("in {}", frame.function),
+    }).collect()
+  }
+
+  // A single line of JSON for editors and CI: code, message, file, span and notes
+  pub fn to_json(&self, filename: &str) -> String {
+    let span = match self.span {
+      Some(span) => serde_json::json!({
+        "start_line": span.start_line,
+        "start_col": span.start_col,
+        "end_line": span.end_line,
+        "end_col": span.end_col,
+      }),
+      None => serde_json::Value::Null,
+    };
+    serde_json::json!({
+      "code": self.kind.code(),
+      "message": self.kind.to_string(),
+      "file": filename,
+      "span": span,
+      "notes": self.notes(filename),
+    }).to_string()
+  }
+}
+
+impl fmt::Display for AsaError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "error[{}]: {}", self.kind.code(), self.kind)
+  }
+}
+
+impl std::error::Error for AsaError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    Some(&self.kind)
+  }
+}
+
+impl From<AsaErrorKind> for AsaError {
+  fn from(kind: AsaErrorKind) -> AsaError {
+    AsaError::new(kind)
+  }
+}
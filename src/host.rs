@@ -0,0 +1,256 @@
+// Rust values handed to scripts as objects: `order.id` reads a property, `order.id = 2` sets one
+// and `order.total()` calls a method, each dispatched to the HostObject implementation.
+
+use std::any::Any;
+use std::cell::{Ref, RefCell, RefMut};
+use std::fmt;
+use std::rc::Rc;
+
+use crate::error::*;
+use crate::interpreter::*;
+use num_bigint::BigInt;
+use rust_decimal::Decimal;
+
+// Lets a `dyn HostObject` be turned back into its concrete type, implemented for every type
+pub trait AsAny {
+  fn as_any(&self) -> &dyn Any;
+  fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: Any> AsAny for T {
+  fn as_any(&self) -> &dyn Any {
+    self
+  }
+
+  fn as_any_mut(&mut self) -> &mut dyn Any {
+    self
+  }
+}
+
+pub trait HostObject: AsAny + fmt::Debug {
+  // Used in error messages and when the object is printed, e.g. `<Order>`
+  fn type_name(&self) -> &str;
+
+  fn get_property(&self, name: &str) -> Result<Value, AsaErrorKind> {
+    Err(AsaErrorKind::Generic(format!("{} has no property {}", self.type_name(), name)))
+  }
+
+  fn set_property(&mut self, name: &str, _value: Value) -> Result<(), AsaErrorKind> {
+    Err(AsaErrorKind::Generic(format!("Cannot set property {} on {}", name, self.type_name())))
+  }
+
+  fn call_method(&mut self, name: &str, _args: &[Value]) -> Result<Value, AsaErrorKind> {
+    Err(AsaErrorKind::UnknownMethod { type_name: self.type_name().to_string(), method: name.to_string() })
+  }
+}
+
+// A shared handle to a host object. Copies of the value refer to the same object, so changes
+// made by a script are visible to the host and the other way around.
+#[derive(Clone)]
+pub struct HostRef(Rc<RefCell<dyn HostObject>>);
+
+impl HostRef {
+  pub fn new(object: impl HostObject + 'static) -> HostRef {
+    HostRef(Rc::new(RefCell::new(object)))
+  }
+
+  pub fn borrow(&self) -> Ref<'_, dyn HostObject> {
+    self.0.borrow()
+  }
+
+  pub fn borrow_mut(&self) -> RefMut<'_, dyn HostObject> {
+    self.0.borrow_mut()
+  }
+
+  // The object as its concrete type, if it is a `T`
+  pub fn downcast_ref<T: HostObject + 'static>(&self) -> Option<Ref<'_, T>> {
+    Ref::filter_map(self.0.borrow(), |object| object.as_any().downcast_ref::<T>()).ok()
+  }
+
+  pub fn downcast_mut<T: HostObject + 'static>(&self) -> Option<RefMut<'_, T>> {
+    RefMut::filter_map(self.0.borrow_mut(), |object| object.as_any_mut().downcast_mut::<T>()).ok()
+  }
+}
+
+impl fmt::Debug for HostRef {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    self.0.borrow().fmt(f)
+  }
+}
+
+// Host objects are compared by identity
+impl PartialEq for HostRef {
+  fn eq(&self, other: &HostRef) -> bool {
+    Rc::ptr_eq(&self.0, &other.0)
+  }
+}
+
+// Conversions between Rust types and asa values, for host functions and methods
+pub trait IntoValue {
+  fn into_value(self) -> Value;
+}
+
+pub trait FromValue: Sized {
+  fn from_value(value: &Value) -> Result<Self, AsaErrorKind>;
+}
+
+fn expected(type_name: &str, value: &Value) -> AsaErrorKind {
+  AsaErrorKind::TypeMismatch(format!("Expected {}, got {}", type_name, value.repr()))
+}
+
+impl IntoValue for Value {
+  fn into_value(self) -> Value {
+    self
+  }
+}
+
+impl FromValue for Value {
+  fn from_value(value: &Value) -> Result<Value, AsaErrorKind> {
+    Ok(value.clone())
+  }
+}
+
+impl IntoValue for i32 {
+  fn into_value(self) -> Value {
+    Value::Number(self)
+  }
+}
+
+impl FromValue for i32 {
+  fn from_value(value: &Value) -> Result<i32, AsaErrorKind> {
+    match value {
+      Value::Number(n) => Ok(*n),
+      Value::BigInt(_) => Err(AsaErrorKind::NumberOverflow),
+      _ => Err(expected("an int", value)),
+    }
+  }
+}
+
+impl IntoValue for i64 {
+  fn into_value(self) -> Value {
+    Interpreter::normalize_int(BigInt::from(self))
+  }
+}
+
+impl FromValue for i64 {
+  fn from_value(value: &Value) -> Result<i64, AsaErrorKind> {
+    match value {
+      Value::Number(n) => Ok(*n as i64),
+      Value::BigInt(n) => i64::try_from(n).map_err(|_| AsaErrorKind::NumberOverflow),
+      _ => Err(expected("an int", value)),
+    }
+  }
+}
+
+impl IntoValue for BigInt {
+  fn into_value(self) -> Value {
+    Interpreter::normalize_int(self)
+  }
+}
+
+impl FromValue for BigInt {
+  fn from_value(value: &Value) -> Result<BigInt, AsaErrorKind> {
+    match value {
+      Value::Number(n) => Ok(BigInt::from(*n)),
+      Value::BigInt(n) => Ok(n.clone()),
+      _ => Err(expected("an int", value)),
+    }
+  }
+}
+
+impl IntoValue for f64 {
+  fn into_value(self) -> Value {
+    Value::Float(self)
+  }
+}
+
+impl FromValue for f64 {
+  fn from_value(value: &Value) -> Result<f64, AsaErrorKind> {
+    match value {
+      Value::Float(f) => Ok(*f),
+      Value::Number(n) => Ok(*n as f64),
+      _ => Err(expected("a float", value)),
+    }
+  }
+}
+
+impl IntoValue for Decimal {
+  fn into_value(self) -> Value {
+    Value::Decimal(self)
+  }
+}
+
+impl FromValue for Decimal {
+  fn from_value(value: &Value) -> Result<Decimal, AsaErrorKind> {
+    match value {
+      Value::Float(_) => Err(expected("a decimal", value)),
+      other => Interpreter::as_decimal(other),
+    }
+  }
+}
+
+impl IntoValue for bool {
+  fn into_value(self) -> Value {
+    Value::Bool(self)
+  }
+}
+
+impl FromValue for bool {
+  fn from_value(value: &Value) -> Result<bool, AsaErrorKind> {
+    match value {
+      Value::Bool(b) => Ok(*b),
+      _ => Err(expected("a bool", value)),
+    }
+  }
+}
+
+impl IntoValue for String {
+  fn into_value(self) -> Value {
+    Value::String(self.into())
+  }
+}
+
+impl IntoValue for &str {
+  fn into_value(self) -> Value {
+    Value::String(self.into())
+  }
+}
+
+impl FromValue for String {
+  fn from_value(value: &Value) -> Result<String, AsaErrorKind> {
+    match value {
+      Value::String(s) => Ok(s.to_string()),
+      _ => Err(expected("a string", value)),
+    }
+  }
+}
+
+impl<T: IntoValue> IntoValue for Vec<T> {
+  fn into_value(self) -> Value {
+    Value::array(self.into_iter().map(IntoValue::into_value).collect())
+  }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+  fn from_value(value: &Value) -> Result<Vec<T>, AsaErrorKind> {
+    match value {
+      Value::Array(items) => items.borrow().iter().map(T::from_value).collect(),
+      _ => Err(expected("an array", value)),
+    }
+  }
+}
+
+impl IntoValue for HostRef {
+  fn into_value(self) -> Value {
+    Value::HostObject(self)
+  }
+}
+
+impl FromValue for HostRef {
+  fn from_value(value: &Value) -> Result<HostRef, AsaErrorKind> {
+    match value {
+      Value::HostObject(object) => Ok(object.clone()),
+      _ => Err(expected("a host object", value)),
+    }
+  }
+}
@@ -1,62 +1,533 @@
 use crate::parser::Node;
+use crate::lexer::Span;
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::fmt;
+use std::iter;
+use std::io::{self, Write};
+use crate::output::OutputBuffer;
+use crate::builtins::*;
+use crate::host::*;
+use crate::methods::*;
+use crate::array::*;
+use crate::map::*;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Instant;
+use crate::limits::*;
 use crate::error::*;
 use std::hash::{Hash, Hasher};
 use std::collections::hash_map::DefaultHasher;
+use num_bigint::{BigInt, Sign};
+use num_traits::{Signed, ToPrimitive, Zero};
+use rust_decimal::{Decimal, RoundingStrategy};
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Value {
-  String(String),
-  Array(Vec<Value>),
+  String(Rc<str>),
+  Array(Array),
+  Map(Map),
   Number(i32),
+  BigInt(BigInt),
+  Float(f64),
+  Decimal(Decimal),
   Bool(bool),
   Identifier(u64),
-  Function {
-    params: Vec<(u64, Option<Node>)>,
-    body: Box<Node>,
-  },
+  Function(Arc<FunctionDef>),
+  Closure(Rc<Closure>),
+  NativeFunction(Rc<NativeFunction>),
+  HostObject(HostRef),
 }
 
-type Frame = HashMap<u64, Value>;
+// How a value reads when printed or joined onto a string: strings are shown as-is
+impl fmt::Display for Value {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Value::String(s) => write!(f, "{}", s),
+      Value::Array(arr) => arr.fmt_items(f, |item, f| write!(f, "{}", item.repr())),
+      Value::Map(map) => map.fmt_entries(f, |item, f| write!(f, "{}", item.repr())),
+      Value::Number(n) => write!(f, "{}", n),
+      Value::BigInt(n) => write!(f, "{}", n),
+      Value::Float(x) => write!(f, "{}", Interpreter::float_to_string(*x)),
+      Value::Decimal(d) => write!(f, "{}", d),
+      Value::Bool(b) => write!(f, "{}", b),
+      Value::Identifier(id) => write!(f, "<id:{}>", id),
+      Value::Function(def) => write!(f, "<fn {}/{}>", def.name, def.params.len()),
+      Value::Closure(closure) => write!(f, "<fn {}/{}>", closure.def.name, closure.def.params.len()),
+      Value::NativeFunction(native) => write!(f, "<native fn {}/{}>", native.name, native.arity),
+      Value::HostObject(object) => write!(f, "<{}>", object.borrow().type_name()),
+    }
+  }
+}
+
+impl Value {
+  pub fn array(items: Vec<Value>) -> Value {
+    Value::Array(Array::new(items))
+  }
+
+  pub fn map(entries: Vec<(MapKey, Value)>) -> Value {
+    Value::Map(Map::new(entries.into_iter().collect()))
+  }
+
+  // A copy no later change can reach: arrays and maps are copied all the way down, everything
+  // else is either immutable or, like host objects, deliberately shared
+  pub fn deep_copy(&self) -> Value {
+    self.deep_copy_with(&mut HashMap::new())
+  }
+
+  // `copies` maps the address of each collection copied so far to its copy, so one that contains
+  // itself (only possible with reference semantics) ends up containing its own copy
+  fn deep_copy_with(&self, copies: &mut HashMap<usize, Value>) -> Value {
+    match self {
+      Value::Array(arr) => {
+        if let Some(copy) = copies.get(&arr.addr()) {
+          return copy.clone();
+        }
+        let copy = Array::default();
+        copies.insert(arr.addr(), Value::Array(copy.clone()));
+        let items = grow_stack(|| arr.borrow().iter().map(|item| item.deep_copy_with(copies)).collect());
+        *copy.borrow_mut() = items;
+        Value::Array(copy)
+      }
+      Value::Map(map) => {
+        if let Some(copy) = copies.get(&map.addr()) {
+          return copy.clone();
+        }
+        let copy = Map::default();
+        copies.insert(map.addr(), Value::Map(copy.clone()));
+        let entries = grow_stack(|| map.borrow().iter().map(|(key, value)| (key.clone(), value.deep_copy_with(copies))).collect());
+        *copy.borrow_mut() = entries;
+        Value::Map(copy)
+      }
+      other => other.clone(),
+    }
+  }
+
+  // Gives an array or map its own copy if it shares it with another value, see `Array::make_unique`
+  pub fn make_unique(&mut self) {
+    match self {
+      Value::Array(arr) => arr.make_unique(),
+      Value::Map(map) => map.make_unique(),
+      _ => {}
+    }
+  }
+
+  // Wraps a Rust object so scripts can use its properties and methods
+  pub fn host(object: impl HostObject + 'static) -> Value {
+    Value::HostObject(HostRef::new(object))
+  }
+
+  // The name of the value's type, as used in error messages
+  pub fn type_name(&self) -> &'static str {
+    match self {
+      Value::String(_) => "string",
+      Value::Array(_) => "array",
+      Value::Map(_) => "map",
+      Value::Number(_) | Value::BigInt(_) => "int",
+      Value::Float(_) => "float",
+      Value::Decimal(_) => "decimal",
+      Value::Bool(_) => "bool",
+      Value::Identifier(_) => "identifier",
+      Value::Function(_) | Value::Closure(_) | Value::NativeFunction(_) => "function",
+      Value::HostObject(_) => "object",
+    }
+  }
+
+  // How a value reads as source: like Display, but strings are quoted so `["1"]` and `[1]` differ,
+  // and decimals keep their suffix so `[2d]` and `[2]` do too
+  pub fn repr(&self) -> String {
+    match self {
+      Value::String(s) => {
+        let escaped = s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n").replace('\t', "\\t");
+        format!("\"{}\"", escaped)
+      }
+      Value::Decimal(d) => format!("{}d", d),
+      other => other.to_string(),
+    }
+  }
+}
+
+// A function as written in the source. Shared between every value referring to it, and between
+// interpreters instantiated from the same CompiledProgram.
+#[derive(Debug, PartialEq)]
+pub struct FunctionDef {
+  pub name: String,
+  pub params: Vec<(u64, Option<Node>)>,
+  pub body: Node,
+}
+
+// What a for loop goes through, as (index, value) pairs
+type LoopItems = Box<dyn Iterator<Item = (Value, Value)>>;
+
+// The variables of one scope. Shared, so a closure keeps the scope it was created in alive and
+// sees later assignments to it.
+pub type Scope = Rc<RefCell<HashMap<u64, Value>>>;
+
+// A function created by a `fn (x) { ... }` or `|x| ...` expression, together with the scopes it
+// was created in, innermost first. The globals are never captured since they're always visible.
+pub struct Closure {
+  pub def: Arc<FunctionDef>,
+  pub captured: Vec<Scope>,
+}
+
+// Captured scopes can hold the closure itself, so closures are printed without them
+impl fmt::Debug for Closure {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "Closure({}/{})", self.def.name, self.def.params.len())
+  }
+}
+
+// Closures are equal when they're the same function over the same scopes
+impl PartialEq for Closure {
+  fn eq(&self, other: &Closure) -> bool {
+    Arc::ptr_eq(&self.def, &other.def) &&
+      self.captured.len() == other.captured.len() &&
+      self.captured.iter().zip(&other.captured).all(|(a, b)| Rc::ptr_eq(a, b))
+  }
+}
+
+// How control leaves a statement: falling through with its value, or jumping via break/continue/return
+#[derive(Debug, PartialEq, Clone)]
+pub enum ControlFlow {
+  Next(Value),
+  Break,
+  Continue,
+  Return(Value),
+}
+
+// Somewhere a value can be written back to, like `rows[i]` or `order.items`, with its index
+// expressions already evaluated
+enum Place {
+  Variable(u64, String),
+  Index(Box<Place>, Value),
+  Property(Box<Place>, String),
+}
+
+// The place as it would be written in source, e.g. `grid[1][2]`, for errors about one level of it
+impl fmt::Display for Place {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Place::Variable(_, name) => write!(f, "{}", name),
+      Place::Index(base, index) => write!(f, "{}[{}]", base, index.repr()),
+      Place::Property(base, name) => write!(f, "{}.{}", base, name),
+    }
+  }
+}
+
+// One active call: its own variables, plus the scopes captured by the closure being run. Each for
+// loop iteration or match arm being run adds a block scope, innermost last, for the variables it
+// binds.
+#[derive(Debug, Default)]
+struct Frame {
+  locals: Scope,
+  blocks: Vec<Scope>,
+  captured: Vec<Scope>,
+}
+
+// Space left on the native stack below which evaluation continues on a new segment, and the size
+// of each new segment
+const STACK_RED_ZONE: usize = 256 * 1024;
+const STACK_SEGMENT: usize = 4 * 1024 * 1024;
+
+// Runs `f` on a fresh heap-allocated stack segment if the native stack is about to run out, for
+// code that recurses once per level of a script's calls or nested values
+pub(crate) fn grow_stack<T>(f: impl FnOnce() -> T) -> T {
+  stacker::maybe_grow(STACK_RED_ZONE, STACK_SEGMENT, f)
+}
+
+// The largest big integer `^` builds, in bits (about 315,000 decimal digits)
+const MAX_BIGINT_BITS: u64 = 1 << 20;
 
-#[derive(Debug)]
 pub struct Interpreter {
   stack: Vec<Frame>,
+  // Native functions, looked up after every frame so scripts can shadow them
+  builtins: HashMap<u64, Value>,
+  limits: Limits,
+  usage: Usage,
+  array_semantics: ArraySemantics,
+  stdout: Box<dyn Write>,
+  stderr: Box<dyn Write>,
+}
+
+impl fmt::Debug for Interpreter {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.debug_struct("Interpreter").field("stack", &self.stack).finish_non_exhaustive()
+  }
+}
+
+impl Default for Interpreter {
+  fn default() -> Self {
+    Self::new()
+  }
 }
 
 impl Interpreter {
   pub fn new() -> Interpreter {
-    let mut stack = Vec::new();
-    stack.push(HashMap::new());
-    Interpreter {
+    let stack = vec![Frame::default()];
+    let mut interpreter = Interpreter {
       stack,
+      builtins: HashMap::new(),
+      limits: Limits::default(),
+      usage: Usage::default(),
+      array_semantics: ArraySemantics::default(),
+      stdout: Box::new(io::stdout()),
+      stderr: Box::new(io::stderr()),
+    };
+    register_defaults(&mut interpreter);
+    interpreter
+  }
+
+  // Replaces the interpreter's limits and starts counting usage from zero
+  pub fn set_limits(&mut self, limits: Limits) {
+    self.limits = limits;
+    self.usage = Usage::default();
+  }
+
+  pub fn usage(&self) -> &Usage {
+    &self.usage
+  }
+
+  // Whether arrays are shared between the variables they're assigned to, see ArraySemantics
+  pub fn set_array_semantics(&mut self, semantics: ArraySemantics) {
+    self.array_semantics = semantics;
+  }
+
+  pub fn array_semantics(&self) -> ArraySemantics {
+    self.array_semantics
+  }
+
+  // Spends one step of fuel, checking the deadline every so often since reading the clock isn't free
+  fn tick(&mut self) -> Result<(), AsaErrorKind> {
+    self.usage.steps += 1;
+    if let Some(fuel) = self.limits.fuel {
+      if self.usage.steps > fuel {
+        return Err(AsaErrorKind::OutOfFuel(fuel));
+      }
+    }
+    if let Some(deadline) = self.limits.deadline {
+      if self.usage.steps & 0xff == 0 && Instant::now() >= deadline {
+        return Err(AsaErrorKind::DeadlineExceeded);
+      }
+    }
+    Ok(())
+  }
+
+  // Accounts for a newly built string, big integer, array or map
+  fn charge_memory(&mut self, value: &Value) -> Result<(), AsaErrorKind> {
+    self.charge_bytes(Self::memory_size(value))
+  }
+
+  // What a string, big integer, array or map counts for against the memory limit, not counting its elements
+  pub(crate) fn memory_size(value: &Value) -> usize {
+    match value {
+      Value::String(s) => s.len(),
+      Value::BigInt(n) => n.bits().div_ceil(8) as usize,
+      Value::Array(arr) => arr.len() * std::mem::size_of::<Value>(),
+      Value::Map(map) => map.len() * (std::mem::size_of::<MapKey>() + std::mem::size_of::<Value>()),
+      _ => 0,
     }
   }
 
+  pub(crate) fn charge_bytes(&mut self, bytes: usize) -> Result<(), AsaErrorKind> {
+    self.usage.memory += bytes;
+    match self.limits.max_memory {
+      Some(max) if self.usage.memory > max => Err(AsaErrorKind::MemoryLimitExceeded(max)),
+      _ => Ok(()),
+    }
+  }
+
+  // Makes a Rust function callable from asa under `name`, replacing any builtin of that name
+  pub fn register_builtin<F>(&mut self, name: &str, arity: Arity, func: F)
+    where F: Fn(&mut Interpreter, &[Value]) -> Result<Value, AsaErrorKind> + 'static {
+    let native = Value::NativeFunction(Rc::new(NativeFunction::new(name, arity, func)));
+    self.builtins.insert(Self::hash_identifier(name.as_bytes()), native);
+  }
+
+  // The sink `print` writes to
+  pub fn stdout(&mut self) -> &mut dyn Write {
+    &mut *self.stdout
+  }
+
+  // The sink `eprint` writes to
+  pub fn stderr(&mut self) -> &mut dyn Write {
+    &mut *self.stderr
+  }
+
+  // Sends `print` output somewhere other than the process's stdout
+  pub fn set_stdout(&mut self, sink: Box<dyn Write>) {
+    self.stdout = sink;
+  }
+
+  // Sends `eprint` output somewhere other than the process's stderr
+  pub fn set_stderr(&mut self, sink: Box<dyn Write>) {
+    self.stderr = sink;
+  }
+
+  // Collects `print` output in memory from now on, returning the buffer to read it from
+  pub fn capture_stdout(&mut self) -> OutputBuffer {
+    let buffer = OutputBuffer::new();
+    self.stdout = Box::new(buffer.clone());
+    buffer
+  }
+
+  // Collects `eprint` output in memory from now on, returning the buffer to read it from
+  pub fn capture_stderr(&mut self) -> OutputBuffer {
+    let buffer = OutputBuffer::new();
+    self.stderr = Box::new(buffer.clone());
+    buffer
+  }
+
   pub fn hash_identifier(name: &[u8]) -> u64 {
     let mut hasher = DefaultHasher::new();
     name.hash(&mut hasher);
     hasher.finish()
   }
 
+  fn frame(&self) -> &Frame {
+    self.stack.last().expect("the globals frame is never popped")
+  }
+
+  fn frame_mut(&mut self) -> &mut Frame {
+    self.stack.last_mut().expect("the globals frame is never popped")
+  }
+
+  // The scopes visible to the running code, innermost first: its block scopes, its own variables,
+  // those captured by the closure being run, then the globals. Callers' locals are never visible,
+  // so lookups don't get slower however deep the call stack gets.
+  fn scopes(&self) -> impl Iterator<Item = &Scope> {
+    let frame = self.frame();
+    let globals = (self.stack.len() > 1).then(|| &self.stack[0].locals);
+    frame.blocks.iter().rev().chain(iter::once(&frame.locals)).chain(frame.captured.iter()).chain(globals)
+  }
+
+  // The innermost scope, where new variables are defined
+  fn scope(&self) -> &Scope {
+    let frame = self.frame();
+    frame.blocks.last().unwrap_or(&frame.locals)
+  }
+
   pub fn get_variable(&self, id: u64) -> Result<Value, AsaErrorKind> {
-    for frame in self.stack.iter().rev() {
-      if let Some(val) = frame.get(&id) {
+    for scope in self.scopes() {
+      if let Some(val) = scope.borrow().get(&id) {
         return Ok(val.clone());
       }
     }
-    Err(AsaErrorKind::UndefinedFunction)
+    self.builtins.get(&id).cloned().ok_or(AsaErrorKind::UndefinedFunction)
+  }
+
+  // Reads a variable named in the script, so a missing one is reported by name
+  fn read_variable(&self, id: u64, name: &str) -> Result<Value, AsaErrorKind> {
+    self.get_variable(id).map_err(|_| AsaErrorKind::VariableNotDefined(name.to_string()))
   }
 
+  // Assigns to the nearest scope that already has the variable, or defines it in the current one
   fn set_variable(&mut self, id: u64, val: Value) {
-    if let Some(frame) = self.stack.last_mut() {
-      frame.insert(id, val);
+    let scope = self.scopes()
+      .find(|scope| scope.borrow().contains_key(&id))
+      .unwrap_or(self.scope());
+    scope.borrow_mut().insert(id, val);
+  }
+
+  // Binds a variable in the current scope, shadowing any outer one (`let`, parameters)
+  fn define_variable(&mut self, id: u64, val: Value) {
+    self.scope().borrow_mut().insert(id, val);
+  }
+
+  // Binds a variable in the outermost frame, where top-level definitions live
+  pub fn set_global(&mut self, id: u64, val: Value) {
+    self.stack[0].locals.borrow_mut().insert(id, val);
+  }
+
+  fn eval_number(value: &[u8]) -> Value {
+    let s = String::from_utf8_lossy(value);
+    match s.parse::<i32>() {
+      Ok(n) => Value::Number(n),
+      // Literals too long for an i32 start out as big integers
+      Err(_) => Value::BigInt(s.parse::<BigInt>().unwrap()),
     }
   }
 
-  fn eval_number(value: &[u8]) -> i32 {
+  // Big integers are only kept while they don't fit in an i32, so equal integers are always
+  // represented the same way
+  pub fn normalize_int(n: BigInt) -> Value {
+    match n.to_i32() {
+      Some(small) => Value::Number(small),
+      None => Value::BigInt(n),
+    }
+  }
+
+  fn as_bigint(val: &Value) -> Option<BigInt> {
+    match val {
+      Value::Number(n) => Some(BigInt::from(*n)),
+      Value::BigInt(n) => Some(n.clone()),
+      _ => None,
+    }
+  }
+
+  fn eval_float(value: &[u8]) -> f64 {
     let s = String::from_utf8_lossy(value);
-    s.parse::<i32>().unwrap()
+    s.parse::<f64>().unwrap()
+  }
+
+  // Integers are promoted to floats whenever they meet one in arithmetic
+  fn as_float(val: &Value) -> Option<f64> {
+    match val {
+      Value::Number(n) => Some(*n as f64),
+      Value::BigInt(n) => n.to_f64(),
+      Value::Float(f) => Some(*f),
+      _ => None,
+    }
+  }
+
+  // Decimals mix exactly with integers but never with floats, which would silently lose precision
+  pub fn as_decimal(val: &Value) -> Result<Decimal, AsaErrorKind> {
+    match val {
+      Value::Number(n) => Ok(Decimal::from(*n)),
+      Value::BigInt(n) => Decimal::from_str_exact(&n.to_string()).map_err(|_| AsaErrorKind::NumberOverflow),
+      Value::Decimal(d) => Ok(*d),
+      Value::Float(_) => Err(AsaErrorKind::TypeMismatch(
+        "Cannot mix decimal and float; convert with decimal() or float() first".to_string()
+      )),
+      _ => Err(AsaErrorKind::TypeMismatch("Expected a number".to_string())),
+    }
+  }
+
+  pub fn rounding_strategy(mode: &str) -> Result<RoundingStrategy, AsaErrorKind> {
+    match mode {
+      "half_even" => Ok(RoundingStrategy::MidpointNearestEven),
+      "half_up" => Ok(RoundingStrategy::MidpointAwayFromZero),
+      "half_down" => Ok(RoundingStrategy::MidpointTowardZero),
+      "up" => Ok(RoundingStrategy::AwayFromZero),
+      "down" => Ok(RoundingStrategy::ToZero),
+      "ceiling" => Ok(RoundingStrategy::ToPositiveInfinity),
+      "floor" => Ok(RoundingStrategy::ToNegativeInfinity),
+      _ => Err(AsaErrorKind::UnknownRoundingMode(mode.to_string())),
+    }
+  }
+
+  // Always keeps a decimal point so floats stay distinguishable from integers when printed
+  pub fn float_to_string(f: f64) -> String {
+    if f.is_finite() && f.fract() == 0.0 {
+      format!("{:.1}", f)
+    } else {
+      f.to_string()
+    }
+  }
+
+  // Numbers compare by value across int and float, so `1 == 1.0` holds and NaN equals nothing
+  pub fn values_equal(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+      (Value::Decimal(_), _) | (_, Value::Decimal(_)) => {
+        match (Self::as_decimal(left), Self::as_decimal(right)) {
+          (Ok(l_num), Ok(r_num)) => l_num == r_num,
+          _ => false,
+        }
+      }
+      (Value::Number(_) | Value::BigInt(_), Value::Float(_)) | (Value::Float(_), Value::Number(_) | Value::BigInt(_)) => {
+        Self::as_float(left) == Self::as_float(right)
+      }
+      _ => left == right,
+    }
   }
 
   fn eval_binary_op(&self, op: &[u8], left: Value, right: Value) -> Result<Value, AsaErrorKind> {
@@ -66,62 +537,80 @@ impl Interpreter {
     if op_str == "+" {
       match (left, right) {
         (Value::String(lhs), Value::String(rhs)) => {
-          return Ok(Value::String(lhs + &rhs));
+          return Ok(Value::String(format!("{}{}", lhs, rhs).into()));
         }
         (Value::String(lhs), r_val) => {
-          let rhs = match r_val {
-            Value::String(s) => s,
-            Value::Number(n) => n.to_string(),
-            Value::Bool(b) => b.to_string(),
-            Value::Identifier(id) => format!("<id:{}>", id),
-            Value::Array(arr) => format!("{:?}", arr),
-            Value::Function{..} => "<function>".to_string(),
-          };
-          return Ok(Value::String(lhs + &rhs));
+          return Ok(Value::String(format!("{}{}", lhs, r_val).into()));
         }
         (l_val, Value::String(rhs)) => {
-          let lhs = match l_val {
-            Value::String(s) => s,
-            Value::Number(n) => n.to_string(),
-            Value::Bool(b) => b.to_string(),
-            Value::Identifier(id) => format!("<id:{}>", id),
-            Value::Array(arr) => format!("{:?}", arr),
-            Value::Function{..} => "<function>".to_string(),
-          };
-          return Ok(Value::String(lhs + &rhs));
+          return Ok(Value::String(format!("{}{}", l_val, rhs).into()));
         }
-        (Value::Number(l_num), Value::Number(r_num)) => {
-          return Ok(Value::Number(l_num + r_num));
+        (l_val @ (Value::Number(_)|Value::BigInt(_)|Value::Float(_)|Value::Decimal(_)), r_val @ (Value::Number(_)|Value::BigInt(_)|Value::Float(_)|Value::Decimal(_))) => {
+          return self.eval_numeric_op(op_str, l_val, r_val);
         }
-        (Value::Bool(_)|Value::Number(_)|Value::Identifier(_)|Value::Array(_)|Value::Function{..}, Value::Bool(_)|Value::Number(_)|Value::Identifier(_)|Value::Array(_)|Value::Function{..}) => {
+        _ => {
           return Err(AsaErrorKind::TypeMismatch("Invalid types for `+` operation".to_string()));
         }
       }
     }
 
     match (left, right, op_str) {
-      (l_generic, r_generic, "==") => Ok(Value::Bool(l_generic == r_generic)),
-      (l_generic, r_generic, "!=") => Ok(Value::Bool(l_generic != r_generic)),
+      (l_generic, r_generic, "==") => Ok(Value::Bool(Self::values_equal(&l_generic, &r_generic))),
+      (l_generic, r_generic, "!=") => Ok(Value::Bool(!Self::values_equal(&l_generic, &r_generic))),
 
-      (Value::Number(l_num), Value::Number(r_num), _) => {
+      (l_val @ (Value::Number(_)|Value::BigInt(_)|Value::Float(_)|Value::Decimal(_)), r_val @ (Value::Number(_)|Value::BigInt(_)|Value::Float(_)|Value::Decimal(_)), _) => {
+        self.eval_numeric_op(op_str, l_val, r_val)
+      }
+      (Value::Bool(l_bool), Value::Bool(r_bool), _) => {
         let result = match op_str {
-          "-" => Value::Number(l_num - r_num),
-          "*" => Value::Number(l_num * r_num),
+          "&&" => Value::Bool(l_bool && r_bool),
+          "||" => Value::Bool(l_bool || r_bool),
+          _ => return Err(AsaErrorKind::Generic("Unknown operator for booleans".to_string())),
+        };
+        Ok(result)
+      }
+      _ => Err(AsaErrorKind::TypeMismatch(
+        "Type error in binary expression: expected matching types".to_string(),
+      )),
+    }
+  }
+
+  fn eval_numeric_op(&self, op_str: &str, left: Value, right: Value) -> Result<Value, AsaErrorKind> {
+    match (left, right) {
+      (l_val @ Value::Decimal(_), r_val) | (l_val, r_val @ Value::Decimal(_)) => {
+        Self::eval_decimal_op(op_str, Self::as_decimal(&l_val)?, Self::as_decimal(&r_val)?)
+      }
+      (Value::Number(l_num), Value::Number(r_num)) => {
+        // Stay on the fast i32 path until an operation overflows, then redo it with big integers
+        let promote = || Self::eval_bigint_op(op_str, BigInt::from(l_num), BigInt::from(r_num));
+        let result = match op_str {
+          "+" => return l_num.checked_add(r_num).map(Value::Number).map_or_else(promote, Ok),
+          "-" => return l_num.checked_sub(r_num).map(Value::Number).map_or_else(promote, Ok),
+          "*" => return l_num.checked_mul(r_num).map(Value::Number).map_or_else(promote, Ok),
           "%" => {
             if r_num == 0 {
-              return Err(AsaErrorKind::Generic("Modulo by zero".to_string()));
+              return Err(AsaErrorKind::DivisionByZero);
             }
-            Value::Number(l_num % r_num)
+            return l_num.checked_rem(r_num).map(Value::Number).map_or_else(promote, Ok);
           },
           "/" => {
             if r_num == 0 {
-              return Err(AsaErrorKind::Generic("Division by zero".to_string()));
+              return Err(AsaErrorKind::DivisionByZero);
+            }
+            // Only exact quotients stay integers, anything else becomes a float. i32::MIN / -1
+            // overflows both the remainder and the quotient, so it goes to big integers.
+            match l_num.checked_rem(r_num) {
+              Some(0) => return l_num.checked_div(r_num).map(Value::Number).map_or_else(promote, Ok),
+              Some(_) => Value::Float(l_num as f64 / r_num as f64),
+              None => return promote(),
             }
-            Value::Number(l_num / r_num)
           },
           "^" => {
-            let val = i32::pow(l_num, r_num as u32);
-            Value::Number(val)
+            if r_num < 0 {
+              Value::Float((l_num as f64).powi(r_num))
+            } else {
+              return l_num.checked_pow(r_num as u32).map(Value::Number).map_or_else(promote, Ok);
+            }
           }
           "<" => Value::Bool(l_num < r_num),
           ">" => Value::Bool(l_num > r_num),
@@ -131,548 +620,1077 @@ impl Interpreter {
         };
         Ok(result)
       }
-      (Value::Bool(l_bool), Value::Bool(r_bool), _) => {
+      (l_val @ (Value::Number(_)|Value::BigInt(_)), r_val @ (Value::Number(_)|Value::BigInt(_))) => {
+        Self::eval_bigint_op(op_str, Self::as_bigint(&l_val).unwrap(), Self::as_bigint(&r_val).unwrap())
+      }
+      (l_val, r_val) => {
+        // Mixed or float operands follow IEEE 754, so comparisons involving NaN are always false
+        let l_num = Self::as_float(&l_val).unwrap_or(f64::NAN);
+        let r_num = Self::as_float(&r_val).unwrap_or(f64::NAN);
         let result = match op_str {
-          "&&" => Value::Bool(l_bool && r_bool),
-          "||" => Value::Bool(l_bool || r_bool),
-          _ => return Err(AsaErrorKind::Generic("Unknown operator for booleans".to_string())),
+          "+" => Value::Float(l_num + r_num),
+          "-" => Value::Float(l_num - r_num),
+          "*" => Value::Float(l_num * r_num),
+          "/" => Value::Float(l_num / r_num),
+          "%" => Value::Float(l_num % r_num),
+          "^" => Value::Float(l_num.powf(r_num)),
+          "<" => Value::Bool(l_num < r_num),
+          ">" => Value::Bool(l_num > r_num),
+          "<=" => Value::Bool(l_num <= r_num),
+          ">=" => Value::Bool(l_num >= r_num),
+          _ => return Err(AsaErrorKind::Generic("Unknown operator".to_string())),
         };
         Ok(result)
       }
-      _ => Err(AsaErrorKind::TypeMismatch(
-        "Type error in binary expression: expected matching types".to_string(),
-      )),
     }
   }
 
+  fn eval_decimal_op(op_str: &str, l_num: Decimal, r_num: Decimal) -> Result<Value, AsaErrorKind> {
+    let result = match op_str {
+      "+" => Value::Decimal(l_num.checked_add(r_num).ok_or(AsaErrorKind::NumberOverflow)?),
+      "-" => Value::Decimal(l_num.checked_sub(r_num).ok_or(AsaErrorKind::NumberOverflow)?),
+      "*" => Value::Decimal(l_num.checked_mul(r_num).ok_or(AsaErrorKind::NumberOverflow)?),
+      "%" => {
+        if r_num.is_zero() {
+          return Err(AsaErrorKind::DivisionByZero);
+        }
+        Value::Decimal(l_num.checked_rem(r_num).ok_or(AsaErrorKind::NumberOverflow)?)
+      },
+      "/" => {
+        // Rounds half-even at full precision (28 digits); use div() to pick scale and rounding
+        if r_num.is_zero() {
+          return Err(AsaErrorKind::DivisionByZero);
+        }
+        Value::Decimal(l_num.checked_div(r_num).ok_or(AsaErrorKind::NumberOverflow)?.normalize())
+      },
+      "^" => {
+        let exp = if r_num.fract().is_zero() { r_num.to_i32() } else { None };
+        let exp = match exp {
+          Some(exp) if exp >= 0 => exp,
+          _ => return Err(AsaErrorKind::TypeMismatch("Decimal exponent must be a non-negative integer".to_string())),
+        };
+        // Square and multiply, so even `1d ^ 2000000000` takes a few dozen steps
+        let (mut result, mut base, mut exp) = (Decimal::ONE, l_num, exp as u32);
+        while exp > 0 {
+          if exp & 1 == 1 {
+            result = result.checked_mul(base).ok_or(AsaErrorKind::NumberOverflow)?;
+          }
+          exp >>= 1;
+          if exp > 0 {
+            base = base.checked_mul(base).ok_or(AsaErrorKind::NumberOverflow)?;
+          }
+        }
+        Value::Decimal(result)
+      }
+      "<" => Value::Bool(l_num < r_num),
+      ">" => Value::Bool(l_num > r_num),
+      "<=" => Value::Bool(l_num <= r_num),
+      ">=" => Value::Bool(l_num >= r_num),
+      _ => return Err(AsaErrorKind::Generic("Unknown operator".to_string())),
+    };
+    Ok(result)
+  }
+
+  fn eval_bigint_op(op_str: &str, l_num: BigInt, r_num: BigInt) -> Result<Value, AsaErrorKind> {
+    let result = match op_str {
+      "+" => Self::normalize_int(l_num + r_num),
+      "-" => Self::normalize_int(l_num - r_num),
+      "*" => Self::normalize_int(l_num * r_num),
+      "%" => {
+        if r_num.is_zero() {
+          return Err(AsaErrorKind::DivisionByZero);
+        }
+        Self::normalize_int(l_num % r_num)
+      },
+      "/" => {
+        if r_num.is_zero() {
+          return Err(AsaErrorKind::DivisionByZero);
+        }
+        if (&l_num % &r_num).is_zero() {
+          Self::normalize_int(l_num / r_num)
+        } else {
+          Value::Float(l_num.to_f64().unwrap_or(f64::NAN) / r_num.to_f64().unwrap_or(f64::NAN))
+        }
+      },
+      "^" => {
+        if r_num.is_negative() {
+          Value::Float(l_num.to_f64().unwrap_or(f64::NAN).powf(r_num.to_f64().unwrap_or(f64::NAN)))
+        } else if l_num.bits() <= 1 {
+          // 0, 1 and -1 stay small whatever the exponent
+          match (l_num.sign(), r_num.is_zero(), r_num.bit(0)) {
+            (Sign::NoSign, false, _) => Value::Number(0),
+            (Sign::Minus, _, true) => Value::Number(-1),
+            _ => Value::Number(1),
+          }
+        } else {
+          // The result has about `bits * exp` bits, so refuse one too big to build in one step
+          match r_num.to_u64() {
+            Some(exp) if l_num.bits().saturating_mul(exp) <= MAX_BIGINT_BITS => Self::normalize_int(l_num.pow(exp as u32)),
+            _ => return Err(AsaErrorKind::NumberOverflow),
+          }
+        }
+      }
+      "<" => Value::Bool(l_num < r_num),
+      ">" => Value::Bool(l_num > r_num),
+      "<=" => Value::Bool(l_num <= r_num),
+      ">=" => Value::Bool(l_num >= r_num),
+      _ => return Err(AsaErrorKind::Generic("Unknown operator".to_string())),
+    };
+    Ok(result)
+  }
+
   fn eval_unary_op(&self, op: &[u8], val: Value) -> Result<Value, AsaErrorKind> {
     let op_str = std::str::from_utf8(op).unwrap();
     match (op_str, val) {
       ("+", Value::Number(n)) => Ok(Value::Number(n)),
-      ("-", Value::Number(n)) => Ok(Value::Number(-n)),
+      ("-", Value::Number(n)) => Ok(n.checked_neg().map(Value::Number).unwrap_or_else(|| Self::normalize_int(-BigInt::from(n)))),
+      ("+", Value::BigInt(n)) => Ok(Value::BigInt(n)),
+      ("-", Value::BigInt(n)) => Ok(Self::normalize_int(-n)),
+      ("+", Value::Float(f)) => Ok(Value::Float(f)),
+      ("+", Value::Decimal(d)) => Ok(Value::Decimal(d)),
+      ("-", Value::Decimal(d)) => Ok(Value::Decimal(-d)),
+      ("-", Value::Float(f)) => Ok(Value::Float(-f)),
       ("!", Value::Number(n)) => Ok(Value::Number(!n)),
       ("!", Value::Bool(b)) => Ok(Value::Bool(!b)),
-      _ => Err(AsaErrorKind::Generic("Type error in unary expression".to_string())),
+      (op, other) => Err(AsaErrorKind::TypeMismatch(format!("Cannot apply unary {} to {}", op, other.type_name()))),
+    }
+  }
+
+  pub fn call_function(&mut self, func_val: Value, arg_nodes: &[Node]) -> Result<Value, AsaError> {
+    // Arguments are evaluated in the caller's scope before the callee's frame exists
+    let mut args = Vec::new();
+    for arg in arg_nodes {
+      args.push(self.eval(arg)?);
     }
+    self.call_function_at(func_val, args, None)
+  }
+
+  // Calls a function value with arguments that have already been evaluated
+  pub fn call_value(&mut self, func_val: Value, args: Vec<Value>) -> Result<Value, AsaError> {
+    self.call_function_at(func_val, args, None)
   }
 
-  pub fn call_function(&mut self, func_val: Value, arg_nodes: &[Node]) -> Result<Value, AsaErrorKind> {
+  fn call_function_at(&mut self, func_val: Value, args: Vec<Value>, call_site: Option<Span>) -> Result<Value, AsaError> {
     match func_val {
-      Value::Function { params, body } => {
-        if arg_nodes.len() > params.len() {
-          return Err(AsaErrorKind::Generic(format!(
-            "Function expected {} arguments, got {}",
-            params.len(),
-            arg_nodes.len()
-          )));
-        }
-
-        // Create new frame
-        self.stack.push(HashMap::new());
-
-        for (i, (param_id, default_node)) in params.iter().enumerate() {
-          let val = if i < arg_nodes.len() {
-            // Argument provided by caller
-            self.exec(&arg_nodes[i])?
-          } else {
-            // No argument provided, use default if available
-            if let Some(def_node) = default_node {
-              self.exec(def_node)?
-            } else {
-              return Err(AsaErrorKind::Generic(
-                "Missing argument for parameter without default".to_string()
-              ));
+      Value::NativeFunction(native) => Ok(native.call(self, &args)?),
+      Value::Function(def) => self.call_def(&def, Vec::new(), args, call_site),
+      Value::Closure(closure) => self.call_def(&closure.def, closure.captured.clone(), args, call_site),
+      other => Err(AsaErrorKind::TypeMismatch(format!("{} is not a function", other.repr())).into()),
+    }
+  }
+
+  // Runs an asa function in a new frame that sees the scopes it captured
+  fn call_def(&mut self, def: &FunctionDef, captured: Vec<Scope>, args: Vec<Value>, call_site: Option<Span>) -> Result<Value, AsaError> {
+    if let Some(max) = self.limits.max_call_depth {
+      // The bottom frame holds the globals, every other frame is an active call
+      if self.stack.len() > max {
+        return Err(AsaErrorKind::CallDepthExceeded(max).into());
+      }
+    }
+    if args.len() > def.params.len() {
+      return Err(AsaErrorKind::WrongArgumentCount(format!(
+        "Function expected {} arguments, got {}",
+        def.params.len(),
+        args.len()
+      )).into());
+    }
+
+    // Create new frame
+    self.stack.push(Frame { locals: Scope::default(), blocks: Vec::new(), captured });
+    let flow = self.bind_arguments(&def.params, args).and_then(|_| self.exec_flow(&def.body));
+    self.stack.pop();
+
+    let result = match flow {
+      Ok(ControlFlow::Next(val)) | Ok(ControlFlow::Return(val)) => Ok(val),
+      Ok(ControlFlow::Break) => Err(AsaErrorKind::BreakOutsideLoop.into()),
+      Ok(ControlFlow::Continue) => Err(AsaErrorKind::ContinueOutsideLoop.into()),
+      Err(e) => Err(e),
+    };
+    // Record this function in the traceback as the error unwinds through it
+    result.map_err(|mut e: AsaError| {
+      e.trace.push(TraceFrame { function: def.name.clone(), call_site });
+      e
+    })
+  }
+
+  // Turns a FunctionDefine (or FunctionExpression) node's name and children into a callable definition
+  pub fn function_def(name: &[u8], children: &[Node]) -> Result<FunctionDef, AsaErrorKind> {
+    // children[0] = FunctionArguments (containing ArgumentDefine nodes)
+    // children[1] = FunctionStatements
+    let mut params = Vec::new();
+    if let Node::FunctionArguments { children: param_nodes } = &children[0] {
+      for param_node in param_nodes {
+        // param_node is Node::ArgumentDefine { children: [...] }
+        if let Node::ArgumentDefine { children: arg_children } = param_node {
+          // arg_children[0] should be an Identifier
+          let (arg_id, default_node) = match arg_children.as_slice() {
+            [Node::Identifier { value }] => {
+              let arg_id = Self::hash_identifier(value);
+              (arg_id, None)
+            }
+            [Node::Identifier { value }, default_expr] => {
+              let arg_id = Self::hash_identifier(value);
+              (arg_id, Some(default_expr.clone()))
             }
+            _ => return Err(AsaErrorKind::Generic("Invalid parameter definition".to_string())),
           };
-          self.set_variable(*param_id, val);
+          params.push((arg_id, default_node));
+        } else {
+          return Err(AsaErrorKind::Generic("Invalid argument node in function definition".to_string()));
         }
+      }
+    }
 
-        let result = match self.exec(&body) {
-          Ok(val) => val,
-          Err(AsaErrorKind::ReturnSignal(ret_val)) => ret_val,
-          Err(e) => {
-            self.stack.pop();
-            return Err(e);
-          }
-        };
+    Ok(FunctionDef {
+      name: String::from_utf8_lossy(name).to_string(),
+      params,
+      body: children[1].clone(),
+    })
+  }
 
-        self.stack.pop();
-        Ok(result)
-      }
-      _ => Err(AsaErrorKind::Generic("Attempted to call a non-function value".to_string())),
+  fn bind_arguments(&mut self, params: &[(u64, Option<Node>)], args: Vec<Value>) -> Result<(), AsaError> {
+    let mut args = args.into_iter();
+    for (param_id, default_node) in params {
+      let val = match args.next() {
+        // Argument provided by caller
+        Some(val) => val,
+        // No argument provided, use default if available
+        None => match default_node {
+          Some(def_node) => self.eval(def_node)?,
+          None => return Err(AsaErrorKind::WrongArgumentCount(
+            "Missing argument for parameter without default".to_string()
+          ).into()),
+        },
+      };
+      self.define_variable(*param_id, val);
     }
+    Ok(())
   }
 
+  // Evaluates a node, discarding where an error happened; see `eval` for the full error
   pub fn exec(&mut self, node: &Node) -> Result<Value,AsaErrorKind> {
+    self.eval(node).map_err(|e| e.kind)
+  }
+
+  pub fn eval(&mut self, node: &Node) -> Result<Value, AsaError> {
+    self.tick()?;
+    // Deep asa recursion nests eval calls
+    grow_stack(|| self.eval_node(node))
+  }
+
+  fn eval_node(&mut self, node: &Node) -> Result<Value, AsaError> {
     match node {
-      Node::Program{children} => {
-        let mut last = Value::Bool(true); // default if empty
-        for n in children {
-          let val = match self.exec(n) {
-            Ok(val) => val,
-            Err(AsaErrorKind::ReturnSignal(val)) => {
-              // Stop executing further and return this value immediately
-              return Ok(val);
-            },
-            Err(e) => return Err(e),
-          };
-          last = val;
+      Node::Program{..} | Node::Block{..} | Node::Statement{..} | Node::FunctionStatements{..} |
+      Node::IfExpression{..} | Node::WhileLoop{..} | Node::ForLoop{..} | Node::Match{..} | Node::FunctionReturn{..} | Node::Break | Node::Continue => {
+        // A `return` that reaches this point ends the program (or gives an if-expression its value),
+        // while a `break` or `continue` that escaped every loop is an error
+        match self.exec_flow(node)? {
+          ControlFlow::Next(val) | ControlFlow::Return(val) => Ok(val),
+          ControlFlow::Break => Err(AsaErrorKind::BreakOutsideLoop.into()),
+          ControlFlow::Continue => Err(AsaErrorKind::ContinueOutsideLoop.into()),
         }
-        Ok(last)
       }
-
       Node::Expression{children} => {
-        self.exec(&children[0])
+        self.eval(&children[0])
       }
       Node::Number{value} => {
-        Ok(Value::Number(Self::eval_number(value)))
+        Ok(Self::eval_number(value))
+      }
+      Node::Float{value} => {
+        Ok(Value::Float(Self::eval_float(value)))
+      }
+      Node::Decimal{value} => {
+        let s = String::from_utf8_lossy(value);
+        Decimal::from_str_exact(&s)
+          .map(Value::Decimal)
+          .map_err(|_| AsaErrorKind::NumberOverflow.into())
       }
       Node::String{value} => {
-        Ok(Value::String(String::from_utf8_lossy(value).to_string()))
+        Ok(Value::String(String::from_utf8_lossy(value).into()))
       }
       Node::Bool{value} => {
         Ok(Value::Bool(*value))
       }
       Node::Identifier{value} => {
         let id = Self::hash_identifier(value);
-        self.get_variable(id)
-      },
-      Node::VariableDefine{children} => {
-        // children[0] = identifier
-        // children[1] = expression
-        if let Node::Identifier{value} = &children[0] {
-          let var_id = Self::hash_identifier(value);
-          let val = self.exec(&children[1])?;
-          self.set_variable(var_id, val.clone());
-          Ok(val)
-        } else {
-          Err(AsaErrorKind::Generic("Invalid variable define".to_string()))
-        }
-      },
-      Node::Statement{children} => {
-        let mut last = Value::Bool(true);
-        for c in children {
-          last = self.exec(c)?;
-        }
-        Ok(last)
-      }
-      Node::BinaryExpression{name, children} => {
-        let left_val = self.exec(&children[0])?;
-        let right_val = self.exec(&children[1])?;
-        self.eval_binary_op(name, left_val, right_val)
+        Ok(self.read_variable(id, &String::from_utf8_lossy(value))?)
       },
+      Node::VariableDefine{children} => self.eval_variable_define(children),
+      Node::BinaryExpression{name, children} => self.eval_binary_expression(name, children),
       Node::UnaryExpression{name, children} => {
-        let val = self.exec(&children[0])?;
-        self.eval_unary_op(name, val)
+        let val = self.eval(&children[0])?;
+        Ok(self.eval_unary_op(name, val)?)
       },
       Node::FunctionDefine{name, children} => {
-        // children[0] = FunctionArguments (now containing ArgumentDefine nodes)
-        // children[1] = FunctionStatements
-        let func_name_id = Self::hash_identifier(name);
-
-        let mut params = Vec::new();
-        if let Node::FunctionArguments { children: param_nodes } = &children[0] {
-          for param_node in param_nodes {
-            // param_node is Node::ArgumentDefine { children: [...] }
-            if let Node::ArgumentDefine { children: arg_children } = param_node {
-              // arg_children[0] should be an Identifier
-              let (arg_id, default_node) = match arg_children.as_slice() {
-                [Node::Identifier { value }] => {
-                  let arg_id = Self::hash_identifier(value);
-                  (arg_id, None)
-                }
-                [Node::Identifier { value }, default_expr] => {
-                  let arg_id = Self::hash_identifier(value);
-                  (arg_id, Some(default_expr.clone()))
-                }
-                _ => return Err(AsaErrorKind::Generic("Invalid parameter definition".to_string())),
-              };
-              params.push((arg_id, default_node));
-            } else {
-              return Err(AsaErrorKind::Generic("Invalid argument node in function definition".to_string()));
-            }
-          }
-        }
-
-        let body_node = &children[1];
-
-        let func_value = Value::Function {
-          params,
-          body: Box::new(body_node.clone()),
-        };
-
-        self.set_variable(func_name_id, func_value);
+        let def = Self::function_def(name, children)?;
+        self.define_variable(Self::hash_identifier(name), Value::Function(Arc::new(def)));
         Ok(Value::Bool(true))
       },
+      Node::FunctionExpression{def} => Ok(self.eval_function_expression(def)),
       Node::FunctionArguments {..} => {
         // Should not be executed on its own
-        Err(AsaErrorKind::Generic("FunctionArguments node should not be executed directly".to_string()))
+        Err(AsaErrorKind::Generic("FunctionArguments node should not be executed directly".to_string()).into())
       },
       Node::ArgumentDefine {..} => {
         // Should not be executed on its own
-        Err(AsaErrorKind::Generic("ArgumentDefine node should not be executed directly".to_string()))
+        Err(AsaErrorKind::Generic("ArgumentDefine node should not be executed directly".to_string()).into())
       },
-      Node::FunctionStatements { children } => {
-        // Run all children and return last (if no return encountered)
-        let mut last = Value::Bool(true);
-        for c in children {
-          match self.exec(c) {
-            Ok(val) => last = val,
-            Err(AsaErrorKind::ReturnSignal(val)) => return Err(AsaErrorKind::ReturnSignal(val)),
-            Err(e) => return Err(e),
-          }
-        }
-        Ok(last)
+      Node::Range {..} => {
+        // Only iterated over by a for loop, or matched against
+        Err(AsaErrorKind::Generic("Range node should not be executed directly".to_string()).into())
       },
-      Node::IfExpression { children } => {
-        // children layout:
-        // [if_condition, if_block, else_if_condition, else_if_block, ..., else_block(optional)]
-        //
-        // Conditions and blocks come in pairs. For each condition-block pair:
-        // - even index: condition
-        // - odd index: block
-        //
-        // If there's an extra child at the end (odd number of children overall),
-        // that last child is the else block.
-
-        let mut index = 0;
-        while index < children.len() - 1 {
-          let condition_value = self.exec(&children[index])?;
-          match condition_value {
-            Value::Bool(true) => {
-              // Condition matched: execute this block
-              return self.exec(&children[index + 1]);
-            },
-            Value::Bool(false) => {
-              // Condition not met, move to next pair
-            },
-            _ => {
-              return Err(AsaErrorKind::TypeMismatch(
-                "If/Else-If condition must be boolean".to_string()
-              ));
-            }
-          }
-          index += 2;
+      Node::MatchArm {..} | Node::PatternAlternatives {..} | Node::ArrayPattern {..} | Node::Wildcard => {
+        // Only used by `exec_match`
+        Err(AsaErrorKind::Generic("Pattern node should not be executed directly".to_string()).into())
+      },
+      Node::ArrayLiteral { children } => self.eval_array_literal(children),
+      Node::MapLiteral { children } => self.eval_map_literal(children),
+      Node::IndexAccess { children } => self.eval_index_access(children),
+      Node::PropertyAccess { children } => self.eval_property_access(children),
+      Node::MethodCall { name, children } => self.eval_method_call(name, children),
+      Node::FunctionCall{name, span, children} => self.eval_function_call(name, *span, children),
+      Node::Call{span, children} => self.eval_call(*span, children),
+      Node::Assignment{children} => self.eval_assignment(children),
+      Node::CompoundAssignment{name, children} => self.eval_compound_assignment(name, children),
+      Node::Null => {
+        Ok(Value::Bool(true))
+      },
+    }
+  }
+
+  fn eval_variable_define(&mut self, children: &[Node]) -> Result<Value, AsaError> {
+    // children[0] = identifier
+    // children[1] = expression
+    if let Node::Identifier{value} = &children[0] {
+      let var_id = Self::hash_identifier(value);
+      let val = self.eval(&children[1])?;
+      self.define_variable(var_id, val.clone());
+      Ok(val)
+    } else {
+      Err(AsaErrorKind::Generic("Invalid variable define".to_string()).into())
+    }
+  }
+
+  fn eval_function_expression(&self, def: &Arc<FunctionDef>) -> Value {
+    let frame = self.frame();
+    let blocks = frame.blocks.iter().rev();
+    // At the top level only block scopes need capturing, the globals are visible anyway
+    let captured = if self.stack.len() > 1 {
+      blocks.chain(iter::once(&frame.locals)).chain(frame.captured.iter()).cloned().collect()
+    } else {
+      blocks.cloned().collect()
+    };
+    Value::Closure(Rc::new(Closure { def: def.clone(), captured }))
+  }
+
+  fn eval_binary_expression(&mut self, name: &[u8], children: &[Node]) -> Result<Value, AsaError> {
+    let left_val = self.eval(&children[0])?;
+    // `&&` and `||` only evaluate their right operand when the left one doesn't decide the result
+    match (name, &left_val) {
+      (b"&&", Value::Bool(false)) => return Ok(Value::Bool(false)),
+      (b"||", Value::Bool(true)) => return Ok(Value::Bool(true)),
+      _ => {}
+    }
+    let right_val = self.eval(&children[1])?;
+    let result = self.eval_binary_op(name, left_val, right_val)?;
+    self.charge_memory(&result)?;
+    Ok(result)
+  }
+
+  fn eval_array_literal(&mut self, children: &[Node]) -> Result<Value, AsaError> {
+    let mut arr = Vec::new();
+    for c in children {
+      let val = self.eval(c)?;
+      arr.push(val);
+    }
+    let arr = Value::array(arr);
+    self.charge_memory(&arr)?;
+    Ok(arr)
+  }
+
+  fn eval_map_literal(&mut self, children: &[Node]) -> Result<Value, AsaError> {
+    // children alternate between keys and values. A repeated key keeps its first position and
+    // its last value.
+    let mut entries = Entries::new();
+    for pair in children.chunks(2) {
+      let key = MapKey::from_value(&self.eval(&pair[0])?)?;
+      let val = self.eval(&pair[1])?;
+      entries.insert(key, val);
+    }
+    let map = Value::Map(Map::new(entries));
+    self.charge_memory(&map)?;
+    Ok(map)
+  }
+
+  fn eval_index_access(&mut self, children: &[Node]) -> Result<Value, AsaError> {
+    // children[0] = object, children[1] = index expression
+    let object_val = self.eval(&children[0])?;
+    let index_val = self.eval(&children[1])?;
+    Ok(Self::index_value(object_val, index_val)?)
+  }
+
+  fn index_value(object_val: Value, index_val: Value) -> Result<Value, AsaErrorKind> {
+    if let Value::Map(map) = object_val {
+      let key = MapKey::from_value(&index_val)?;
+      return map.get(&key).ok_or_else(|| AsaErrorKind::KeyNotFound(index_val.repr()));
+    }
+    let idx = match index_val {
+      Value::Number(n) => n,
+      _ => return Err(AsaErrorKind::TypeMismatch("Index must be a number".to_string())),
+    };
+    match object_val {
+      Value::String(s) => {
+        let chars: Vec<char> = s.chars().collect();
+        if idx < 0 || (idx as usize) >= chars.len() {
+          return Err(AsaErrorKind::IndexOutOfRange { index: idx, len: chars.len(), place: None });
+        }
+        Ok(Value::String(chars[idx as usize].to_string().into()))
+      }
+      Value::Array(arr) => {
+        if idx < 0 {
+          return Err(AsaErrorKind::IndexOutOfRange { index: idx, len: arr.len(), place: None });
         }
+        arr.get(idx as usize).ok_or_else(|| AsaErrorKind::IndexOutOfRange { index: idx, len: arr.len(), place: None })
+      }
+      _ => Err(AsaErrorKind::TypeMismatch("Cannot index this type".to_string())),
+    }
+  }
 
-        // If we get here, none of the conditions were true.
-        // Check if there's an else block.
-        if children.len() % 2 == 1 {
-          // There's an extra child at the end, which is the else block
-          self.exec(&children[children.len() - 1])
-        } else {
-          // No else block - return a default value.
-          Ok(Value::Bool(true))
+  fn eval_property_access(&mut self, children: &[Node]) -> Result<Value, AsaError> {
+    // children[0] = object, children[1] = property identifier
+    let object_val = self.eval(&children[0])?;
+    let property_name = Self::property_name(&children[1])?;
+    Ok(Self::property_value(object_val, &property_name)?)
+  }
+
+  fn property_name(node: &Node) -> Result<String, AsaErrorKind> {
+    if let Node::Identifier { value } = node {
+      Ok(String::from_utf8_lossy(value).to_string())
+    } else {
+      Err(AsaErrorKind::Generic("Invalid property name".to_string()))
+    }
+  }
+
+  fn property_value(object_val: Value, property_name: &str) -> Result<Value, AsaErrorKind> {
+    match object_val {
+      Value::String(s) => {
+        match property_name {
+          "length" => Ok(Value::Number(s.chars().count() as i32)),
+          _ => Err(Self::no_property("string", property_name)),
         }
       }
-      Node::WhileLoop{children} => {
-        // children[0] = condition
-        // children[1] = body block
-
-        loop {
-          let condition_value = self.exec(&children[0])?;
-          match condition_value {
-            Value::Bool(true) => {
-              // Condition is true, execute the body block
-              match self.exec(&children[1]) {
-                Ok(_) => {
-                  // Body executed successfully with no break or continue, loop again
-                },
-                Err(AsaErrorKind::BreakSignal) => {
-                  // Break out of the loop
-                  break Ok(Value::Bool(true));
-                },
-                Err(AsaErrorKind::ContinueSignal) => {
-                  // Skip to next iteration (re-check condition)
-                  continue;
-                },
-                Err(e) => {
-                  // Other error, propagate upwards
-                  return Err(e);
-                }
+      Value::Array(arr) => {
+        match property_name {
+          "length" => Ok(Value::Number(arr.len() as i32)),
+          _ => Err(Self::no_property("array", property_name)),
+        }
+      }
+      // `m.name` is `m["name"]`
+      Value::Map(map) => map.get(&property_name.into()).ok_or_else(|| AsaErrorKind::KeyNotFound(Value::String(property_name.into()).repr())),
+      Value::HostObject(object) => object.borrow().get_property(property_name),
+      other => Err(Self::no_property(other.type_name(), property_name)),
+    }
+  }
+
+  fn no_property(type_name: &str, property_name: &str) -> AsaErrorKind {
+    AsaErrorKind::TypeMismatch(format!("{} has no property {}", type_name, property_name))
+  }
+
+  // Whether a node names somewhere a value can be written back to: a variable, or an index or
+  // property of one
+  fn is_place(node: &Node) -> bool {
+    match node {
+      Node::Identifier { .. } => true,
+      Node::IndexAccess { children } | Node::PropertyAccess { children } => Self::is_place(&children[0]),
+      Node::Expression { children } => Self::is_place(&children[0]),
+      _ => false,
+    }
+  }
+
+  // Resolves a place expression (see `is_place`), evaluating its indexes once, left to right
+  fn eval_place(&mut self, node: &Node) -> Result<Place, AsaError> {
+    match node {
+      Node::Identifier { value } => {
+        Ok(Place::Variable(Self::hash_identifier(value), String::from_utf8_lossy(value).to_string()))
+      }
+      Node::IndexAccess { children } => {
+        let base = self.eval_place(&children[0])?;
+        let index = self.eval(&children[1])?;
+        Ok(Place::Index(Box::new(base), index))
+      }
+      Node::PropertyAccess { children } => {
+        let base = self.eval_place(&children[0])?;
+        Ok(Place::Property(Box::new(base), Self::property_name(&children[1])?))
+      }
+      Node::Expression { children } => self.eval_place(&children[0]),
+      _ => Err(AsaErrorKind::Generic("Left side of assignment must be a variable, or an index or property of one".to_string()).into()),
+    }
+  }
+
+  fn read_place(&mut self, place: &Place) -> Result<Value, AsaErrorKind> {
+    match place {
+      Place::Variable(id, name) => self.read_variable(*id, name),
+      Place::Index(base, index) => Self::index_value(self.read_place(base)?, index.clone()).map_err(|err| match err {
+        AsaErrorKind::IndexOutOfRange { index, len, place: None } => {
+          AsaErrorKind::IndexOutOfRange { index, len, place: Some(base.to_string()) }
+        }
+        other => other,
+      }),
+      Place::Property(base, name) => Self::property_value(self.read_place(base)?, name),
+    }
+  }
+
+  // Stores a value in a place. An element or entry is changed in the array or map holding it,
+  // see `change_in_place`.
+  fn write_place(&mut self, place: &Place, val: Value) -> Result<(), AsaErrorKind> {
+    let mut val = Some(val);
+    let mut take = || val.take().expect("a place is only written once");
+    match place {
+      Place::Variable(id, _) => {
+        self.set_variable(*id, take());
+        Ok(())
+      }
+      Place::Index(base, index) => {
+        self.change_in_place(base, &mut |container| {
+          match container {
+            Value::Array(arr) => {
+              let idx = Self::array_index(index)?;
+              let mut items = arr.borrow_mut();
+              let len = items.len();
+              match usize::try_from(idx).ok().and_then(|i| items.get_mut(i)) {
+                Some(item) => *item = take(),
+                None => return Err(AsaErrorKind::IndexOutOfRange { index: idx, len, place: Some(base.to_string()) }),
               }
-            },
-            Value::Bool(false) => {
-              // Condition is false, stop looping and return default value
-              break Ok(Value::Bool(true));
-            },
-            _ => {
-              // If the condition isn't boolean, return a type error
-              return Err(AsaErrorKind::TypeMismatch(
-                "While condition must be boolean".to_string()
-              ));
             }
+            // Assigning to a missing key adds it at the end
+            Value::Map(map) => {
+              map.borrow_mut().insert(MapKey::from_value(index)?, take());
+            }
+            other => return Err(Self::not_indexable(other, base)),
           }
-        }
+          Ok(Value::Bool(true))
+        })?;
+        Ok(())
+      }
+      Place::Property(base, name) => {
+        self.change_in_place(base, &mut |object| {
+          match object {
+            Value::Map(map) => {
+              map.borrow_mut().insert(name.as_str().into(), take());
+            }
+            // Host objects are shared, so setting the property is all there is to do
+            Value::HostObject(object) => object.borrow_mut().set_property(name, take())?,
+            other => return Err(AsaErrorKind::TypeMismatch(format!(
+              "Cannot set property {} on {} `{}`", name, other.type_name(), base
+            ))),
+          }
+          Ok(Value::Bool(true))
+        })?;
+        Ok(())
       }
+    }
+  }
 
-      Node::ArrayLiteral { children } => {
-        let mut arr = Vec::new();
-        for c in children {
-          let val = self.exec(c)?;
-          arr.push(val);
+  // Changes the value held in a place, typically an array or map, without writing the whole
+  // value back. With value semantics each level of the place first gets an array or map of its
+  // own if the one it holds is shared with another value (copy on write), so the change is never
+  // seen through that value and an unshared one isn't copied at all. With reference semantics
+  // the shared one is changed for everyone holding it.
+  fn change_in_place(&mut self, place: &Place, change: &mut dyn FnMut(&mut Value) -> Result<Value, AsaErrorKind>) -> Result<Value, AsaErrorKind> {
+    if self.array_semantics == ArraySemantics::Reference {
+      // Arrays and maps are changed through any handle to them, but a host object's property has
+      // to be set back
+      if let Place::Property(base, name) = place {
+        if let Value::HostObject(object) = self.read_place(base)? {
+          return Self::change_host_property(&object, name, false, change);
         }
-        Ok(Value::Array(arr))
       }
-
-      Node::IndexAccess { children } => {
-        // children[0] = object, children[1] = index expression
-        let object_val = self.exec(&children[0])?;
-        let index_val = self.exec(&children[1])?;
-        let idx = match index_val {
-          Value::Number(n) => n,
-          _ => return Err(AsaErrorKind::TypeMismatch("Index must be a number".to_string())),
+      let mut value = self.read_place(place)?;
+      return change(&mut value);
+    }
+    match place {
+      Place::Variable(id, _) => {
+        let scope = self.scopes().find(|scope| scope.borrow().contains_key(id)).cloned();
+        let Some(scope) = scope else {
+          let mut value = self.read_place(place)?;
+          return change(&mut value);
         };
-        match object_val {
-          Value::String(s) => {
-            let chars: Vec<char> = s.chars().collect();
-            if idx < 0 || (idx as usize) >= chars.len() {
-              return Err(AsaErrorKind::Generic("String index out of range".to_string()));
+        let mut vars = scope.borrow_mut();
+        let value = vars.get_mut(id).expect("the scope was found holding the variable");
+        value.make_unique();
+        change(value)
+      }
+      Place::Index(base, index) => {
+        self.change_in_place(base, &mut |container| {
+          match container {
+            Value::Array(arr) => {
+              let idx = Self::array_index(index)?;
+              let mut items = arr.borrow_mut();
+              let len = items.len();
+              match usize::try_from(idx).ok().and_then(|i| items.get_mut(i)) {
+                Some(item) => {
+                  item.make_unique();
+                  change(item)
+                }
+                None => Err(AsaErrorKind::IndexOutOfRange { index: idx, len, place: Some(base.to_string()) }),
+              }
             }
-            Ok(Value::String(chars[idx as usize].to_string()))
+            Value::Map(map) => {
+              let key = MapKey::from_value(index)?;
+              let mut entries = map.borrow_mut();
+              match entries.get_mut(&key) {
+                Some(entry) => {
+                  entry.make_unique();
+                  change(entry)
+                }
+                None => Err(AsaErrorKind::KeyNotFound(index.repr())),
+              }
+            }
+            other => Err(Self::not_indexable(other, base)),
           }
-          Value::Array(arr) => {
-            if idx < 0 || (idx as usize) >= arr.len() {
-              return Err(AsaErrorKind::Generic("Array index out of range".to_string()));
+        })
+      }
+      Place::Property(base, name) => {
+        self.change_in_place(base, &mut |object| {
+          match object {
+            Value::Map(map) => {
+              let mut entries = map.borrow_mut();
+              match entries.get_mut(&MapKey::from(name.as_str())) {
+                Some(entry) => {
+                  entry.make_unique();
+                  change(entry)
+                }
+                None => Err(AsaErrorKind::KeyNotFound(Value::String(name.as_str().into()).repr())),
+              }
             }
-            Ok(arr[idx as usize].clone())
+            Value::HostObject(object) => Self::change_host_property(object, name, true, change),
+            other => Err(AsaErrorKind::TypeMismatch(format!(
+              "Cannot access property {} on {} `{}`", name, other.type_name(), base
+            ))),
           }
-          _ => Err(AsaErrorKind::TypeMismatch("Cannot index this type".to_string())),
-        }
+        })
       }
+    }
+  }
 
-      Node::PropertyAccess { children } => {
-        // children[0] = object, children[1] = property identifier
-        let object_val = self.exec(&children[0])?;
-        let property_node = &children[1];
-        let property_name = if let Node::Identifier { value } = property_node {
-          String::from_utf8_lossy(value).to_string()
-        } else {
-          return Err(AsaErrorKind::Generic("Invalid property name".to_string()));
-        };
+  // A host object hands out a new value for every read, so change that and set it back
+  fn change_host_property(object: &HostRef, name: &str, unique: bool, change: &mut dyn FnMut(&mut Value) -> Result<Value, AsaErrorKind>) -> Result<Value, AsaErrorKind> {
+    let mut value = object.borrow().get_property(name)?;
+    if unique {
+      value.make_unique();
+    }
+    let result = change(&mut value)?;
+    object.borrow_mut().set_property(name, value)?;
+    Ok(result)
+  }
 
-        match object_val {
-          Value::String(s) => {
-            match property_name.as_str() {
-              "length" => Ok(Value::Number(s.chars().count() as i32)),
+  fn array_index(index: &Value) -> Result<i32, AsaErrorKind> {
+    match index {
+      Value::Number(n) => Ok(*n),
+      _ => Err(AsaErrorKind::TypeMismatch("Index must be a number".to_string())),
+    }
+  }
 
-              _ => Err(AsaErrorKind::Generic("Unknown property on array".to_string()))
-            }
-          }
-          Value::Array(arr) => {
-            match property_name.as_str() {
-              "length" => Ok(Value::Number(arr.len() as i32)),
+  fn not_indexable(value: &Value, place: &Place) -> AsaErrorKind {
+    AsaErrorKind::TypeMismatch(format!("Cannot index into {} `{}`", value.type_name(), place))
+  }
 
-              _ => Err(AsaErrorKind::Generic("Unknown property on array".to_string()))
+  fn eval_method_call(&mut self, name: &[u8], children: &[Node]) -> Result<Value, AsaError> {
+    // A receiver that names a place is resolved once, so that a method changing it can write
+    // the new value back: `rows[i].push(x)` updates `rows`. Anything else is a temporary.
+    let place = if Self::is_place(&children[0]) {
+      Some(self.eval_place(&children[0])?)
+    } else {
+      None
+    };
+    let receiver = match &place {
+      Some(place) => self.read_place(place)?,
+      None => self.eval(&children[0])?,
+    };
+    let mut arg_values = Vec::new();
+    for arg in children.iter().skip(1) {
+      arg_values.push(self.eval(arg)?);
+    }
+
+    let name = String::from_utf8_lossy(name);
+    let changes = changes_receiver(&receiver, &name);
+    // A method changing its receiver only builds what it adds, e.g. the one element of a `push`
+    let before = if changes { Self::memory_size(&receiver) } else { 0 };
+    let result = match &place {
+      Some(place) if changes => {
+        // Changed where it's stored. The copy read above has to go first, or it would count as
+        // sharing the array or map.
+        drop(receiver);
+        self.change_in_place(place, &mut |value| call_method(value, &name, &arg_values))?
+      }
+      _ => {
+        // A temporary may still share its array or map with a variable, e.g. one returned by a
+        // function
+        let mut receiver = receiver;
+        if changes && self.array_semantics == ArraySemantics::Value {
+          receiver.make_unique();
+        }
+        match call_method(&receiver, &name, &arg_values) {
+          // Without a method of that name, `obj.f(x)` calls the function stored in `obj.f`, e.g.
+          // a closure kept in a map
+          Err(err @ AsaErrorKind::UnknownMethod { .. }) => match Self::property_value(receiver, &name) {
+            Ok(func @ (Value::Function(_) | Value::Closure(_) | Value::NativeFunction(_))) => {
+              return self.call_value(func, arg_values);
             }
-          }
-          _ => Err(AsaErrorKind::Generic("Cannot access properties on this type".to_string())),
+            _ => return Err(err.into()),
+          },
+          result => result?,
         }
       }
-      Node::MethodCall { name, children } => {
-        let object_val = self.exec(&children[0])?;
-        let mut arg_values = Vec::new();
-        for arg in children.iter().skip(1) {
-          arg_values.push(self.exec(&arg)?);
-        }
-
-        let method_str = String::from_utf8_lossy(&name).to_string();
+    };
+    self.charge_bytes(Self::memory_size(&result).saturating_sub(before))?;
+    Ok(result)
+  }
 
-        match (object_val, &children[0]) {
-          (Value::Array(mut arr), &Node::Identifier { ref value }) => {
-            match method_str.as_str() {
-              "push" => {
-                if arg_values.len() != 1 {
-                  return Err(AsaErrorKind::Generic("push expects exactly one argument".to_string()));
-                }
-                // Mutate the array
-                arr.push(arg_values[0].clone());
+  fn eval_function_call(&mut self, name: &[u8], span: Span, children: &[Node]) -> Result<Value, AsaError> {
+    // children[0] = FunctionArguments
+    let func_id = Self::hash_identifier(name);
 
-                // Write back into the variable environment so `a` is updated
-                let var_id = Self::hash_identifier(value);
-                self.set_variable(var_id, Value::Array(arr.clone()));
+    let func_val = self.get_variable(func_id)?;
+    let args = self.eval_arguments(&children[0])?;
+    self.call_function_at(func_val, args, Some(span))
+  }
 
-                // Return the new length (or any other value you want)
-                Ok(Value::Array(arr))
-              }
-              "pop" => {
-                if arg_values.len() != 0 {
-                  return Err(AsaErrorKind::Generic("pop expects no arguments".to_string()));
-                }
-                // Mutate the array
-                let popped = arr.pop();
+  // Calls whatever function value an expression produces, e.g. `make()(1)` or `handlers[i](x)`
+  fn eval_call(&mut self, span: Span, children: &[Node]) -> Result<Value, AsaError> {
+    // children[0] = callee, children[1] = FunctionArguments
+    let func_val = self.eval(&children[0])?;
+    let args = self.eval_arguments(&children[1])?;
+    self.call_function_at(func_val, args, Some(span))
+  }
 
-                // Write back into the variable environment so `a` is updated
-                let var_id = Self::hash_identifier(value);
-                self.set_variable(var_id, Value::Array(arr.clone()));
+  // Evaluates the expressions of a FunctionArguments node, left to right
+  fn eval_arguments(&mut self, node: &Node) -> Result<Vec<Value>, AsaError> {
+    let mut args = Vec::new();
+    if let Node::FunctionArguments { children } = node {
+      for arg in children {
+        args.push(self.eval(arg)?);
+      }
+    }
+    Ok(args)
+  }
 
-                // Return the popped value
-                match popped {
-                  Some(val) => Ok(val),
-                  None => Err(AsaErrorKind::Generic("pop on empty array".to_string())),
-                }
-              }
-              "insert" => {
-                if arg_values.len() != 2 {
-                  return Err(AsaErrorKind::Generic("insert expects exactly two arguments".to_string()));
-                }
-                let idx = match arg_values[0] {
-                  Value::Number(n) => n,
-                  _ => return Err(AsaErrorKind::TypeMismatch("Index must be a number".to_string())),
-                };
-                if idx < 0 || (idx as usize) > arr.len() {
-                  return Err(AsaErrorKind::Generic("Array index out of range".to_string()));
-                }
-                arr.insert(idx as usize, arg_values[1].clone());
+  fn eval_assignment(&mut self, children: &[Node]) -> Result<Value, AsaError> {
+    // children[0] = place, e.g. `x`, `grid[i][j]` or `rows[i].name`, children[1] = value
+    let val = self.eval(&children[1])?;
+    let place = self.eval_place(&children[0])?;
+    self.write_place(&place, val.clone())?;
+    Ok(val)
+  }
 
-                // Write back into the variable environment so `a` is updated
-                let var_id = Self::hash_identifier(value);
-                self.set_variable(var_id, Value::Array(arr.clone()));
+  // `place op= value`, and `place++`/`place--` as `place += 1`/`place -= 1`. The place's indexes
+  // are evaluated once, before the value.
+  fn eval_compound_assignment(&mut self, name: &[u8], children: &[Node]) -> Result<Value, AsaError> {
+    let place = self.eval_place(&children[0])?;
+    let current = self.read_place(&place)?;
+    let operand = self.eval(&children[1])?;
+    let val = self.eval_binary_op(name, current, operand)?;
+    self.charge_memory(&val)?;
+    self.write_place(&place, val.clone())?;
+    Ok(val)
+  }
 
-                Ok(Value::Array(arr))
-              }
-              "prepend" => {
-                if arg_values.len() != 1 {
-                  return Err(AsaErrorKind::Generic("prepend expects exactly one argument".to_string()));
-                }
-                arr.insert(0, arg_values[0].clone());
+  // Runs a statement-level node, reporting how control leaves it separately from real errors
+  pub fn exec_flow(&mut self, node: &Node) -> Result<ControlFlow, AsaError> {
+    self.tick()?;
+    match node {
+      Node::Statement{span, children} => {
+        let mut last = ControlFlow::Next(Value::Bool(true));
+        for c in children {
+          last = self.exec_flow(c).map_err(|e| e.at(*span))?;
+          if !matches!(last, ControlFlow::Next(_)) {
+            break;
+          }
+        }
+        Ok(last)
+      }
+      Node::Program{children} | Node::Block{children} | Node::FunctionStatements{children} => {
+        // Run all children and return the last value, unless control leaves early. Earlier
+        // values are dropped straight away: one still holding an array would make the next
+        // statement's change to it copy the whole array.
+        let Some((tail, init)) = children.split_last() else {
+          return Ok(ControlFlow::Next(Value::Bool(true)));
+        };
+        for c in init {
+          match self.exec_flow(c)? {
+            ControlFlow::Next(_) => {}
+            flow => return Ok(flow),
+          }
+        }
+        self.exec_flow(tail)
+      }
+      Node::IfExpression { children } => self.exec_if(children),
+      Node::WhileLoop{children} => self.exec_while(children),
+      Node::ForLoop{children} => self.exec_for(children),
+      Node::Match{children} => self.exec_match(children),
+      Node::FunctionReturn{children} => {
+        let val = self.eval(&children[0])?;
+        Ok(ControlFlow::Return(val))
+      },
+      Node::Break => {
+        Ok(ControlFlow::Break)
+      },
+      Node::Continue => {
+        Ok(ControlFlow::Continue)
+      },
+      _ => Ok(ControlFlow::Next(self.eval(node)?)),
+    }
+  }
 
-                // Write back into the variable environment so `a` is updated
-                let var_id = Self::hash_identifier(value);
-                self.set_variable(var_id, Value::Array(arr.clone()));
+  fn exec_if(&mut self, children: &[Node]) -> Result<ControlFlow, AsaError> {
+    // children layout:
+    // [if_condition, if_block, else_if_condition, else_if_block, ..., else_block(optional)]
+    //
+    // Conditions and blocks come in pairs. For each condition-block pair:
+    // - even index: condition
+    // - odd index: block
+    //
+    // If there's an extra child at the end (odd number of children overall),
+    // that last child is the else block.
 
-                Ok(Value::Array(arr))
-              }
-              _ => Err(AsaErrorKind::Generic(format!("Unknown array method: {}", method_str))),
-            }
-          },
-          (Value::Array(_), _) => {
-            // If the object is not a plain identifier (like a property access), handle accordingly
-            Err(AsaErrorKind::Generic("Method call on non-identifier object not supported yet".to_string()))
-          },
-          _ => Err(AsaErrorKind::Generic("Method calls only implemented for arrays currently".to_string())),
+    let mut index = 0;
+    while index < children.len() - 1 {
+      let condition_value = self.eval(&children[index])?;
+      match condition_value {
+        Value::Bool(true) => {
+          // Condition matched: execute this block
+          return self.exec_flow(&children[index + 1]);
+        },
+        Value::Bool(false) => {
+          // Condition not met, move to next pair
+        },
+        _ => {
+          return Err(AsaErrorKind::TypeMismatch(
+            "If/Else-If condition must be boolean".to_string()
+          ).into());
         }
       }
-      Node::FunctionCall{name, children} => {
-        // children[0] = FunctionArguments
-        let func_id = Self::hash_identifier(name);
-        let func_name_str = String::from_utf8_lossy(name).to_string();
+      index += 2;
+    }
 
-        // Built-in functions
-        if func_name_str == "print" {
-          // print(x)
-          if let Node::FunctionArguments { children: args } = &children[0] {
-            if args.len() != 1 {
-              return Err(AsaErrorKind::Generic("print expects 1 argument".to_string()));
-            }
-            let val = self.exec(&args[0])?;
-            println!("{:?}", val);
-            return Ok(Value::Bool(true));
-          } else {
-            return Err(AsaErrorKind::Generic("Invalid print args".to_string()));
-          }
+    // If we get here, none of the conditions were true.
+    // Check if there's an else block.
+    if children.len() % 2 == 1 {
+      // There's an extra child at the end, which is the else block
+      self.exec_flow(&children[children.len() - 1])
+    } else {
+      // No else block - return a default value.
+      Ok(ControlFlow::Next(Value::Bool(true)))
+    }
+  }
+
+  fn exec_for(&mut self, children: &[Node]) -> Result<ControlFlow, AsaError> {
+    // children[0] = index variable, or Null
+    // children[1] = value variable
+    // children[2] = what to iterate over
+    // children[3] = body block
+    let index_id = match &children[0] {
+      Node::Identifier { value } => Some(Self::hash_identifier(value)),
+      _ => None,
+    };
+    let value_id = match &children[1] {
+      Node::Identifier { value } => Self::hash_identifier(value),
+      _ => return Err(AsaErrorKind::Generic("Invalid loop variable".to_string()).into()),
+    };
+    let items = self.loop_items(&children[2], index_id.is_some())?;
+    self.run_for(index_id, value_id, items, &children[3])
+  }
+
+  // Runs `run` in a new innermost scope holding `bindings`. The variables only exist inside it,
+  // and a closure created there keeps this scope rather than sharing the next one's.
+  fn with_block_scope<T>(&mut self, bindings: impl IntoIterator<Item = (u64, Value)>, run: impl FnOnce(&mut Interpreter) -> Result<T, AsaError>) -> Result<T, AsaError> {
+    let scope = Scope::new(RefCell::new(bindings.into_iter().collect()));
+    self.frame_mut().blocks.push(scope);
+    let result = run(self);
+    self.frame_mut().blocks.pop();
+    result
+  }
+
+  fn run_for(&mut self, index_id: Option<u64>, value_id: u64, items: LoopItems, body: &Node) -> Result<ControlFlow, AsaError> {
+    for (index, item) in items {
+      // Each iteration binds the loop variables afresh
+      let bindings = index_id.map(|id| (id, index)).into_iter().chain([(value_id, item)]);
+      match self.with_block_scope(bindings, |this| this.exec_flow(body))? {
+        ControlFlow::Next(_) | ControlFlow::Continue => {}
+        ControlFlow::Break => break,
+        // Leave the loop and let the enclosing function return
+        ControlFlow::Return(val) => return Ok(ControlFlow::Return(val)),
+      }
+    }
+    Ok(ControlFlow::Next(Value::Bool(true)))
+  }
+
+  // The values a for loop goes through, each with its index: an array's elements, a string's
+  // characters, the integers of a range, or a map's keys in insertion order. With `keyed`, as in
+  // `for k, v in m`, a map gives its values instead, indexed by their keys.
+  fn loop_items(&mut self, node: &Node, keyed: bool) -> Result<LoopItems, AsaError> {
+    if let Node::Range { inclusive, children } = node {
+      let mut bounds = Vec::new();
+      for child in children {
+        match self.eval(child)? {
+          Value::Number(n) => bounds.push(n as i64),
+          other => return Err(AsaErrorKind::TypeMismatch(format!(
+            "Range bounds must be integers, got {}", other.type_name()
+          )).into()),
         }
+      }
+      let (start, end, step) = (bounds[0], bounds[1], bounds.get(2).copied().unwrap_or(1));
+      if step == 0 {
+        return Err(AsaErrorKind::Generic("Range step must not be zero".to_string()).into());
+      }
+      // A negative step counts down towards the end
+      let inclusive = *inclusive;
+      let numbers = (0..).map(move |k| start + k * step).take_while(move |&n| {
+        (inclusive && n == end) || (step > 0 && n < end) || (step < 0 && n > end)
+      });
+      return Ok(Self::indexed(numbers.map(|n| Value::Number(n as i32))));
+    }
+    match self.eval(node)? {
+      // Read one element or entry at a time, so with reference semantics the loop sees changes the
+      // body makes to them, but stop at the length it started with so a body that pushes ends
+      Value::Array(arr) => {
+        let len = arr.len();
+        Ok(Self::indexed((0..len).map_while(move |i| arr.get(i))))
+      }
+      Value::Map(map) => {
+        let len = map.len();
+        let entries = (0..len).map_while(move |i| map.get_index(i));
+        if keyed {
+          Ok(Box::new(entries.map(|(key, value)| (key.to_value(), value))))
+        } else {
+          Ok(Self::indexed(entries.map(|(key, _)| key.to_value())))
+        }
+      }
+      Value::String(s) => {
+        let chars: Vec<char> = s.chars().collect();
+        Ok(Self::indexed(chars.into_iter().map(|c| Value::String(c.to_string().into()))))
+      }
+      other => Err(AsaErrorKind::TypeMismatch(format!("Cannot iterate over {}", other.type_name())).into()),
+    }
+  }
 
-        if func_name_str == "len" {
-          // len(x)
-          return if let Node::FunctionArguments { children: args } = &children[0] {
-            if args.len() != 1 {
-              return Err(AsaErrorKind::Generic("len expects 1 argument".to_string()));
-            }
-            let val = self.exec(&args[0])?;
-            match val {
-              Value::String(s) => Ok(Value::Number(s.chars().count() as i32)),
-              Value::Array(arr) => Ok(Value::Number(arr.len() as i32)),
-              _ => Err(AsaErrorKind::Generic("len() not supported on this type".to_string()))
-            }
-          } else {
-            Err(AsaErrorKind::Generic("Invalid len args".to_string()))
+  fn indexed(items: impl Iterator<Item = Value> + 'static) -> LoopItems {
+    Box::new(items.enumerate().map(|(i, item)| (Value::Number(i as i32), item)))
+  }
+
+  fn exec_match(&mut self, children: &[Node]) -> Result<ControlFlow, AsaError> {
+    // children[0] = value matched on, the rest are arms
+    let value = self.eval(&children[0])?;
+    for arm in &children[1..] {
+      let Node::MatchArm { children: arm } = arm else {
+        return Err(AsaErrorKind::Generic("Invalid match arm".to_string()).into());
+      };
+      // arm[0] = pattern, arm[1] = guard or Null, arm[2] = body
+      let mut bindings = Vec::new();
+      if !self.match_pattern(&arm[0], &value, &mut bindings)? {
+        continue;
+      }
+      // The pattern's variables only exist in the guard and the body
+      let flow = self.with_block_scope(bindings, |this| {
+        if !matches!(arm[1], Node::Null) {
+          match this.eval(&arm[1])? {
+            Value::Bool(true) => {}
+            Value::Bool(false) => return Ok(None),
+            _ => return Err(AsaErrorKind::TypeMismatch("Match guard must be boolean".to_string()).into()),
           }
         }
+        this.exec_flow(&arm[2]).map(Some)
+      })?;
+      if let Some(flow) = flow {
+        return Ok(flow);
+      }
+    }
+    Err(AsaErrorKind::NoMatchingArm(value.repr()).into())
+  }
 
-        let func_val = self.get_variable(func_id)?;
-        // Extract arguments from children[0]
-        let mut arg_nodes = Vec::new();
-        if let Node::FunctionArguments { children: args } = &children[0] {
-          for arg in args {
-            if let Node::Expression { children: exprs } = arg {
-              // Each expression node has one child which is the actual expression
-              arg_nodes.push(exprs[0].clone());
-            } else {
-              arg_nodes.push(arg.clone());
-            }
+  // Whether a value fits a pattern, collecting the variables the pattern binds
+  fn match_pattern(&mut self, pattern: &Node, value: &Value, bindings: &mut Vec<(u64, Value)>) -> Result<bool, AsaError> {
+    match pattern {
+      Node::Wildcard => Ok(true),
+      Node::Identifier { value: name } => {
+        bindings.push((Self::hash_identifier(name), value.clone()));
+        Ok(true)
+      }
+      Node::PatternAlternatives { children } => {
+        for alternative in children {
+          let bound = bindings.len();
+          if self.match_pattern(alternative, value, bindings)? {
+            return Ok(true);
           }
+          bindings.truncate(bound);
         }
-        self.call_function(func_val, &arg_nodes)
-      },
-      Node::Assignment{children} => {
-        let val = self.exec(&children[1])?;
-
-        match &children[0] {
-          Node::Identifier { value } => {
-            // Normal variable assignment
-            let var_id = Self::hash_identifier(value);
-            self.set_variable(var_id, val.clone());
-            Ok(val)
+        Ok(false)
+      }
+      Node::ArrayPattern { children } => {
+        let items = match value {
+          Value::Array(arr) if arr.len() == children.len() => arr.to_vec(),
+          _ => return Ok(false),
+        };
+        for (item_pattern, item) in children.iter().zip(&items) {
+          if !self.match_pattern(item_pattern, item, bindings)? {
+            return Ok(false);
           }
+        }
+        Ok(true)
+      }
+      Node::Range { inclusive, children } => {
+        if !matches!(value, Value::Number(_) | Value::BigInt(_) | Value::Float(_) | Value::Decimal(_)) {
+          return Ok(false);
+        }
+        let start = self.eval(&children[0])?;
+        let end = self.eval(&children[1])?;
+        let below_end: &[u8] = if *inclusive { b"<=" } else { b"<" };
+        let in_range = self.eval_binary_op(b">=", value.clone(), start)? == Value::Bool(true) &&
+          self.eval_binary_op(below_end, value.clone(), end)? == Value::Bool(true);
+        Ok(in_range)
+      }
+      // A literal
+      _ => {
+        let literal = self.eval(pattern)?;
+        Ok(Self::values_equal(&literal, value))
+      }
+    }
+  }
 
-          Node::IndexAccess { children: idx_children } => {
-            // idx_children[0] = the object (should be an identifier if we want to mutate)
-            // idx_children[1] = index expression
-            let idx = match self.exec(&idx_children[1])? {
-              Value::Number(n) => n,
-              _ => return Err(AsaErrorKind::TypeMismatch("Index must be a number".to_string())),
-            };
-
-            // If the base is an identifier, we can mutate the original variable
-            if let Node::Identifier { value: array_name } = &idx_children[0] {
-              let var_id = Self::hash_identifier(array_name);
-              let mut arr = match self.get_variable(var_id)? {
-                Value::Array(a) => a,
-                _ => return Err(AsaErrorKind::TypeMismatch("Cannot index into non-array".to_string())),
-              };
-              if idx < 0 || idx as usize >= arr.len() {
-                return Err(AsaErrorKind::Generic("Array index out of range".to_string()));
-              }
-              arr[idx as usize] = val.clone();
-              // Store the modified array back into the variable
-              self.set_variable(var_id, Value::Array(arr));
-              Ok(val)
-            } else {
-              Err(AsaErrorKind::Generic("Left side of assignment must be a variable or currently unsupported complex expression".parse().unwrap()))
-            }
-          }
+  fn exec_while(&mut self, children: &[Node]) -> Result<ControlFlow, AsaError> {
+    // children[0] = condition
+    // children[1] = body block
 
-          Node::PropertyAccess {..} => {
-            Err(AsaErrorKind::Generic("Property assignment not supported.".parse().unwrap()))
+    loop {
+      let condition_value = self.eval(&children[0])?;
+      match condition_value {
+        Value::Bool(true) => {
+          // Condition is true, execute the body block
+          match self.exec_flow(&children[1])? {
+            ControlFlow::Next(_) | ControlFlow::Continue => {
+              // Loop again (re-check condition)
+            },
+            ControlFlow::Break => {
+              // Break out of the loop
+              break Ok(ControlFlow::Next(Value::Bool(true)));
+            },
+            ControlFlow::Return(val) => {
+              // Leave the loop and let the enclosing function return
+              return Ok(ControlFlow::Return(val));
+            }
           }
-
-          _ => Err(AsaErrorKind::Generic("Invalid lvalue in assignment.".parse().unwrap()))
+        },
+        Value::Bool(false) => {
+          // Condition is false, stop looping and return default value
+          break Ok(ControlFlow::Next(Value::Bool(true)));
+        },
+        _ => {
+          // If the condition isn't boolean, return a type error
+          return Err(AsaErrorKind::TypeMismatch(
+            "While condition must be boolean".to_string()
+          ).into());
         }
       }
-      Node::FunctionReturn{children} => {
-        // Not tested
-        let val = self.exec(&children[0])?;
-        Err(AsaErrorKind::ReturnSignal(val))
-      },
-      Node::Null => {
-        Ok(Value::Bool(true))
-      },
-      Node::Block{children} => {
-        let mut last = Value::Bool(true);
-        for c in children {
-          last = self.exec(c)?;
-        }
-        Ok(last)
-      },
-      Node::Break => {
-        // Not tested
-        Err(AsaErrorKind::BreakSignal)
-      },
-      Node::Continue => {
-        // Not tested
-        Err(AsaErrorKind::ContinueSignal)
-      },
     }
   }
 }
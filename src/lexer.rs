@@ -2,6 +2,8 @@ use nom::*;
 
 use core::iter::*;
 
+use crate::error::*;
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct Token {
   pub kind: TokenKind,
@@ -12,6 +14,12 @@ pub struct Token {
   pub end_col: u32,
 }
 
+impl Default for Token {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
 impl Token {
   pub fn new() -> Token {
     Token{
@@ -32,6 +40,34 @@ impl Token {
     self.kind = new_kind;
   }
 
+  pub fn span(&self) -> Span {
+    Span {
+      start_line: self.start_line,
+      start_col: self.start_col,
+      end_line: self.end_line,
+      end_col: self.end_col,
+    }
+  }
+
+}
+
+// A region of source code, from the first character of one token to the last of another
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Span {
+  pub start_line: u32,
+  pub start_col: u32,
+  pub end_line: u32,
+  pub end_col: u32,
+}
+
+impl Span {
+  pub fn new(start_line: u32, start_col: u32, end_line: u32, end_col: u32) -> Span {
+    Span { start_line, start_col, end_line, end_col }
+  }
+
+  pub fn to(self, end: Span) -> Span {
+    Span { end_line: end.end_line, end_col: end.end_col, ..self }
+  }
 }
 
 
@@ -47,6 +83,10 @@ pub enum TokenKind {
   // ElseIf,
   Else,
   While,
+  For,
+  In,
+  Step,
+  Match,
   //------
   Break,
   Continue,
@@ -60,6 +100,7 @@ pub enum TokenKind {
   RightBracket,
   Equal,
   EqualEqual,
+  FatArrow,
   NotEqual,
   GreaterThan,
   LessThan,
@@ -67,20 +108,32 @@ pub enum TokenKind {
   LessThanOrEqual,
   LogicalAnd,
   LogicalOr,
+  Pipe,
   Not,
   Plus,
   Dash,
   Multiply,
   Exponent,
   Modulus,
+  PlusEqual,
+  DashEqual,
+  MultiplyEqual,
+  SlashEqual,
+  ModulusEqual,
+  ExponentEqual,
+  PlusPlus,
+  DashDash,
   Dot,
   Quote,
   WhiteSpace,
   Semicolon,
   Comma,
+  Colon,
+  Underscore,
   Slash,
   Other,
   StringLiteral,
+  UnterminatedString,
   EOF,
 }
 
@@ -89,6 +142,12 @@ pub struct Tokens {
   pub tokens: Vec<Token>,
 }
 
+impl Default for Tokens {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
 impl Tokens {
   pub fn new() -> Tokens {
     Tokens { tokens: vec![] }
@@ -108,10 +167,7 @@ impl Tokens {
 
   pub fn is_done(&self) -> bool {
     if !self.is_empty() {
-      match &self.tokens[0].kind {
-        TokenKind::EOF => true,
-        _ => false,
-      }
+      matches!(self.tokens[0].kind, TokenKind::EOF)
     } else {
       true
     }
@@ -168,7 +224,6 @@ pub fn lex(input: &str) -> Tokens {
   let mut i = 0;
   let mut line = 1;
   let mut col = 1;
-  let mut diff = 0;
   while i < list.len() {
     let c = list[i];
 
@@ -214,10 +269,9 @@ pub fn lex(input: &str) -> Tokens {
       // If we reached EOF without finding a closing quote, this is an error scenario.
       // For simplicity, let's assume well-formed input. Otherwise, handle as needed:
       if i >= list.len() {
-        // Unterminated string handling could go here
-        // We'll just push what we have as Other or handle error
+        // Keep what we have so `check_tokens` can report where the string started
         let token = Token {
-          kind: TokenKind::StringLiteral,
+          kind: TokenKind::UnterminatedString,
           lexeme: string_content,
           start_line,
           end_line: line,
@@ -245,14 +299,19 @@ pub fn lex(input: &str) -> Tokens {
       continue;
     }
 
+    // Start of the token, multi-character tokens advance `i` past their first character
+    let start = i;
     let mut kind = match c {
       48..=57 => TokenKind::Digit,
       65..=90 | 97..=122 => TokenKind::Alpha,
-      32 | 10 | 9=> TokenKind::WhiteSpace,
+      32 | 10 | 13 | 9=> TokenKind::WhiteSpace,
       61 => { // '='
         if i + 1 < list.len() && list[i + 1] == b'=' {
           i += 1;
           TokenKind::EqualEqual
+        } else if i + 1 < list.len() && list[i + 1] == b'>' {
+          i += 1;
+          TokenKind::FatArrow
         } else {
           TokenKind::Equal
         }
@@ -286,7 +345,7 @@ pub fn lex(input: &str) -> Tokens {
           i += 1;
           TokenKind::LogicalOr
         } else {
-          TokenKind::Other
+          TokenKind::Pipe
         }
       },
       33 => { // '!'
@@ -304,14 +363,46 @@ pub fn lex(input: &str) -> Tokens {
       41 => TokenKind::RightParen,
       91 => TokenKind::LeftBracket,
       93 => TokenKind::RightBracket,
-      43 => TokenKind::Plus,
-      45 => TokenKind::Dash,
+      43 => { // '+'
+        if i + 1 < list.len() && list[i + 1] == b'=' {
+          i += 1;
+          TokenKind::PlusEqual
+        } else if i + 1 < list.len() && list[i + 1] == b'+' {
+          i += 1;
+          TokenKind::PlusPlus
+        } else {
+          TokenKind::Plus
+        }
+      }
+      45 => { // '-'
+        if i + 1 < list.len() && list[i + 1] == b'=' {
+          i += 1;
+          TokenKind::DashEqual
+        } else if i + 1 < list.len() && list[i + 1] == b'-' {
+          i += 1;
+          TokenKind::DashDash
+        } else {
+          TokenKind::Dash
+        }
+      }
+      // *=, /=, ^= and %=
+      42 | 47 | 94 | 37 if i + 1 < list.len() && list[i + 1] == b'=' => {
+        i += 1;
+        match c {
+          42 => TokenKind::MultiplyEqual,
+          47 => TokenKind::SlashEqual,
+          94 => TokenKind::ExponentEqual,
+          _ => TokenKind::ModulusEqual,
+        }
+      }
       42 => TokenKind::Multiply,
       47 => TokenKind::Slash,
       94 => TokenKind::Exponent,
       37 => TokenKind::Modulus,
       46 => TokenKind::Dot,
       44 => TokenKind::Comma,
+      58 => TokenKind::Colon,
+      95 => TokenKind::Underscore,
       34 => TokenKind::Quote,
       _ => TokenKind::Other,
     };
@@ -320,7 +411,6 @@ pub fn lex(input: &str) -> Tokens {
     if (c == b'i') && i + 1 < list.len() && list[i + 1] == b'f' {
       kind = TokenKind::If;
       i += 1;
-      diff = 1;
     }
 
     // check if else
@@ -328,7 +418,6 @@ pub fn lex(input: &str) -> Tokens {
       list[i + 1] == b'l' && list[i + 2] == b's' && list[i + 3] == b'e' {
       kind = TokenKind::Else;
       i += 3;
-      diff = 3;
     }
 
     // check if while
@@ -336,80 +425,61 @@ pub fn lex(input: &str) -> Tokens {
       list[i + 1] == b'h' && list[i + 2] == b'i' && list[i + 3] == b'l' && list[i + 4] == b'e' {
       kind = TokenKind::While;
       i += 4;
-      diff = 4;
     }
 
     //check if fn
-    if c == b'f' {
-      if i + 1 < list.len(){
-        if list[i + 1] == b'n' {
-          kind = TokenKind::Fn;
-          i += 1;
-          diff = 1;
-        }
-      }
+    if c == b'f' && i + 1 < list.len() &&
+      list[i + 1] == b'n' {
+      kind = TokenKind::Fn;
+      i += 1;
     }
     //check if true
-    if c == b't' {
-      if i + 3 < list.len(){
-        if list[i + 1] == b'r' && list[i + 2] == b'u' && list[i + 3] == b'e' {
-          kind = TokenKind::True;
-          i += 3;
-          diff = 3;
-        }
-      }
+    if c == b't' && i + 3 < list.len() &&
+      list[i + 1] == b'r' && list[i + 2] == b'u' && list[i + 3] == b'e' {
+      kind = TokenKind::True;
+      i += 3;
     }
     //check if false
-    if c == b'f' {
-      if i + 4 < list.len(){
-        if list[i + 1] == b'a' && list[i + 2] == b'l' && list[i + 3] == b's' && list[i + 4] == b'e' {
-          kind = TokenKind::False;
-          i += 4;
-          diff = 4;
-        }
-      }
+    if c == b'f' && i + 4 < list.len() &&
+      list[i + 1] == b'a' && list[i + 2] == b'l' && list[i + 3] == b's' && list[i + 4] == b'e' {
+      kind = TokenKind::False;
+      i += 4;
     }
 
     //check if break
-    if c == b'b' {
-      if i + 4 < list.len(){
-        if list[i + 1] == b'r' && list[i + 2] == b'e' && list[i + 3] == b'a' && list[i + 4] == b'k' {
-          kind = TokenKind::Break;
-          i += 4;
-          diff = 4;
-        }
-      }
+    if c == b'b' && i + 4 < list.len() &&
+      list[i + 1] == b'r' && list[i + 2] == b'e' && list[i + 3] == b'a' && list[i + 4] == b'k' {
+      kind = TokenKind::Break;
+      i += 4;
     }
 
     //check if continue
-    if c == b'c' {
-      if i + 7 < list.len(){
-        if list[i + 1] == b'o' && list[i + 2] == b'n' && list[i + 3] == b't' && list[i + 4] == b'i' && list[i + 5] == b'n' && list[i + 6] == b'u' && list[i + 7] == b'e' {
-          kind = TokenKind::Continue;
-          i += 7;
-          diff = 7;
-        }
-      }
+    if c == b'c' && i + 7 < list.len() &&
+      list[i + 1] == b'o' && list[i + 2] == b'n' && list[i + 3] == b't' && list[i + 4] == b'i' && list[i + 5] == b'n' && list[i + 6] == b'u' && list[i + 7] == b'e' {
+      kind = TokenKind::Continue;
+      i += 7;
     }
 
     //check if let
-    if c == b'l' {
-      if i + 2 < list.len(){
-        if list[i + 1] == b'e' && list[i + 2] == b't' {
-          kind = TokenKind::Let;
-          i += 2;
-          diff = 2;
-        }
-      }
+    if c == b'l' && i + 2 < list.len() &&
+      list[i + 1] == b'e' && list[i + 2] == b't' {
+      kind = TokenKind::Let;
+      i += 2;
     }
     //check if return
-    if c == b'r' {
-      if i + 5 < list.len(){
-        if list[i + 1] == b'e' && list[i + 2] == b't' && list[i + 3] == b'u' && list[i + 4] == b'r' && list[i + 5] == b'n' {
-          kind = TokenKind::Return;
-          i += 5;
-          diff = 5;
-        }
+    if c == b'r' && i + 5 < list.len() &&
+      list[i + 1] == b'e' && list[i + 2] == b't' && list[i + 3] == b'u' && list[i + 4] == b'r' && list[i + 5] == b'n' {
+      kind = TokenKind::Return;
+      i += 5;
+    }
+
+    // for, in, step and match only count as whole words, since identifiers like `format`,
+    // `index`, `steps` and `matches` start with them
+    let words = [(&b"for"[..], TokenKind::For), (b"in", TokenKind::In), (b"step", TokenKind::Step), (b"match", TokenKind::Match)];
+    for (word, keyword) in words {
+      if kind == TokenKind::Alpha && is_word_at(list, start, word) {
+        kind = keyword;
+        i += word.len() - 1;
       }
     }
 
@@ -418,12 +488,12 @@ pub fn lex(input: &str) -> Tokens {
       kind,
       lexeme: vec![c],
       start_col: col,
-      end_col: col + diff,
+      end_col: col + (i - start) as u32,
       start_line: line,
       end_line: line,
     };
+    col += (i - start) as u32;
     i +=1;
-    diff = 0;
     tokens.push(token.clone());
     col +=1;
     if c == 10{
@@ -446,3 +516,24 @@ pub fn lex(input: &str) -> Tokens {
   let filtered_tokens: Vec<Token> = tokens.tokens.iter().filter(|tkn| tkn.kind != TokenKind::WhiteSpace).cloned().collect();
   Tokens::from(filtered_tokens)
 }
+
+// Whether `word` is written at `i` with no letter or digit directly before or after it
+fn is_word_at(list: &[u8], i: usize, word: &[u8]) -> bool {
+  let is_alnum = |c: &u8| c.is_ascii_alphanumeric();
+  list[i..].starts_with(word) &&
+    !(i > 0 && is_alnum(&list[i - 1])) &&
+    !list.get(i + word.len()).is_some_and(is_alnum)
+}
+
+// Reports the first token the lexer couldn't make sense of
+pub fn check_tokens(tokens: &Tokens) -> Result<(), AsaError> {
+  for token in &tokens.tokens {
+    let kind = match token.kind {
+      TokenKind::Other => AsaErrorKind::UnexpectedCharacter(token.lexeme[0] as char),
+      TokenKind::UnterminatedString => AsaErrorKind::UnterminatedString,
+      _ => continue,
+    };
+    return Err(AsaError::new(kind).at(token.span()));
+  }
+  Ok(())
+}
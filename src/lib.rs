@@ -4,9 +4,29 @@ pub mod interpreter;
 pub mod parser;
 pub mod error;
 pub mod lexer;
+pub mod validate;
+pub mod output;
+pub mod engine;
+pub mod builtins;
+pub mod host;
+pub mod compiled;
+pub mod limits;
+pub mod methods;
+pub mod array;
+pub mod map;
 
 
 pub use self::parser::*;
 pub use self::interpreter::*;
 pub use self::lexer::*;
-pub use self::error::*;
\ No newline at end of file
+pub use self::error::*;
+pub use self::validate::*;
+pub use self::output::*;
+pub use self::engine::*;
+pub use self::builtins::*;
+pub use self::host::*;
+pub use self::compiled::*;
+pub use self::limits::*;
+pub use self::methods::*;
+pub use self::array::*;
+pub use self::map::*;
\ No newline at end of file
@@ -0,0 +1,30 @@
+// Budgets for running untrusted scripts. Every limit is off by default; once one runs out the
+// interpreter stops with its own error kind, which the host can tell apart from script errors.
+
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Default)]
+pub struct Limits {
+  // Number of evaluation steps, roughly one per node evaluated
+  pub fuel: Option<u64>,
+  // Number of asa function calls that may be active at once
+  pub max_call_depth: Option<usize>,
+  // Bytes of strings and arrays the script may build in total, counted as they're created
+  pub max_memory: Option<usize>,
+  // Point in time after which execution stops
+  pub deadline: Option<Instant>,
+}
+
+impl Limits {
+  // A deadline `timeout` from now
+  pub fn deadline_in(timeout: Duration) -> Option<Instant> {
+    Some(Instant::now() + timeout)
+  }
+}
+
+// What a script has used so far, checked against Limits
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Usage {
+  pub steps: u64,
+  pub memory: usize,
+}
@@ -6,58 +6,82 @@ use std::env;
 use std::fs;
 use std::process;
 
-fn main() -> Result<(), AsaErrorKind> {
+#[derive(Clone, Copy, PartialEq)]
+enum ErrorFormat {
+  Human,
+  Json,
+}
+
+// Prints an error in the requested format and exits
+fn fail(err: AsaError, filename: &str, format: ErrorFormat) -> ! {
+  match format {
+    ErrorFormat::Human => eprintln!("{}", err.render_traceback(filename)),
+    ErrorFormat::Json => eprintln!("{}", err.to_json(filename)),
+  }
+  process::exit(1);
+}
+
+fn main() {
   // Collect command line arguments
   let args: Vec<String> = env::args().collect();
-  if args.len() < 2 {
-    eprintln!("Usage: asa <filename.asa>");
-    process::exit(1);
+
+  // asa explain E0101
+  if args.len() == 3 && args[1] == "explain" {
+    match explain(&args[2]) {
+      Some(text) => println!("{}", text),
+      None => {
+        eprintln!("No explanation for error code {}", args[2]);
+        process::exit(1);
+      }
+    }
+    return;
   }
 
-  let filename = &args[1];
-  // Read the file into a String
-  let source = fs::read_to_string(filename)
-    .map_err(|e| AsaErrorKind::Generic(format!("Could not read file {}: {}", filename, e)))?;
-
-  // Lex the source
-  let tokens = lex(&source);
-
-  // Parse the tokens
-  match program(tokens) {
-    Ok((_, tree)) => {
-      let mut interpreter = Interpreter::new();
-      // First, interpret the entire AST to load all definitions (functions, variables).
-      match interpreter.exec(&tree) {
-        Ok(_top_level_result) => {
-          // Now try to call main
-          let main_id = Interpreter::hash_identifier(b"main");
-          if let Ok(main_func) = interpreter.get_variable(main_id) {
-            // Simulate calling main with no arguments
-            match interpreter.call_function(main_func, &[]) {
-              Ok(result) => {
-                println!("Main returned: {:?}", result);
-                Ok(())
-              },
-              Err(err) => {
-                eprintln!("Runtime Error while calling main: {:?}", err);
-                process::exit(1);
-              }
-            }
-          } else {
-            // If no main function is defined, just proceed
-            println!("No main function found; execution completed.");
-            Ok(())
-          }
-        },
-        Err(err) => {
-          eprintln!("Runtime Error: {:?}", err);
-          process::exit(1);
-        }
+  let mut format = ErrorFormat::Human;
+  let mut filename = None;
+  for arg in &args[1..] {
+    match arg.as_str() {
+      "--error-format=human" => format = ErrorFormat::Human,
+      "--error-format=json" => format = ErrorFormat::Json,
+      _ if filename.is_none() && !arg.starts_with("--") => filename = Some(arg.clone()),
+      _ => {
+        eprintln!("Unknown argument: {}", arg);
+        process::exit(1);
       }
-    },
-    Err(e) => {
-      eprintln!("Parse Error: {:?}", e);
+    }
+  }
+  let filename = match filename {
+    Some(filename) => filename,
+    None => {
+      eprintln!("Usage: asa [--error-format=human|json] <filename.asa>");
+      eprintln!("       asa explain <code>");
       process::exit(1);
     }
+  };
+
+  // Read the file into a String
+  let source = match fs::read_to_string(&filename) {
+    Ok(source) => source,
+    Err(e) => {
+      let kind = AsaErrorKind::Generic(format!("Could not read file {}: {}", filename, e));
+      fail(kind.into(), &filename, format);
+    }
+  };
+
+  // Run the file to load all definitions (functions, variables).
+  let mut engine = Engine::new();
+  if let Err(err) = engine.load(&source) {
+    fail(err, &filename, format);
+  }
+
+  // Now try to call main
+  if engine.get_global("main").is_some() {
+    match engine.call("main", &[]) {
+      Ok(result) => println!("Main returned: {}", result.repr()),
+      Err(err) => fail(err, &filename, format),
+    }
+  } else {
+    // If no main function is defined, just proceed
+    println!("No main function found; execution completed.");
   }
 }
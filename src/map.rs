@@ -0,0 +1,151 @@
+// Maps from keys to values, written `{ "a": 1, b: 2 }`. Like arrays they are held through a
+// shared handle and follow the interpreter's ArraySemantics. Entries keep the order they were
+// first inserted in, so iterating or printing a map is deterministic.
+
+use crate::array::{compare_once, drop_contents, format_once};
+use crate::error::AsaErrorKind;
+use crate::interpreter::{Interpreter, Value};
+use indexmap::IndexMap;
+use std::cell::{Ref, RefCell, RefMut};
+use std::fmt;
+use std::rc::Rc;
+
+// The values a map can be indexed by
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MapKey {
+  String(Rc<str>),
+  Int(i32),
+  Bool(bool),
+}
+
+impl MapKey {
+  pub fn from_value(value: &Value) -> Result<MapKey, AsaErrorKind> {
+    match value {
+      Value::String(s) => Ok(MapKey::String(s.clone())),
+      Value::Number(n) => Ok(MapKey::Int(*n)),
+      Value::Bool(b) => Ok(MapKey::Bool(*b)),
+      other => Err(AsaErrorKind::TypeMismatch(format!(
+        "Map keys must be strings, integers or bools, got {}", other.type_name()
+      ))),
+    }
+  }
+
+  pub fn to_value(&self) -> Value {
+    match self {
+      MapKey::String(s) => Value::String(s.clone()),
+      MapKey::Int(n) => Value::Number(*n),
+      MapKey::Bool(b) => Value::Bool(*b),
+    }
+  }
+}
+
+impl From<&str> for MapKey {
+  fn from(s: &str) -> MapKey {
+    MapKey::String(s.into())
+  }
+}
+
+pub type Entries = IndexMap<MapKey, Value>;
+
+#[derive(Clone, Default)]
+pub struct Map(Rc<RefCell<Entries>>);
+
+impl Map {
+  pub fn new(entries: Entries) -> Map {
+    Map(Rc::new(RefCell::new(entries)))
+  }
+
+  pub fn borrow(&self) -> Ref<'_, Entries> {
+    self.0.borrow()
+  }
+
+  pub fn borrow_mut(&self) -> RefMut<'_, Entries> {
+    self.0.borrow_mut()
+  }
+
+  pub fn len(&self) -> usize {
+    self.0.borrow().len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.0.borrow().is_empty()
+  }
+
+  pub fn get(&self, key: &MapKey) -> Option<Value> {
+    self.0.borrow().get(key).cloned()
+  }
+
+  // The entry at a position in insertion order
+  pub fn get_index(&self, idx: usize) -> Option<(MapKey, Value)> {
+    self.0.borrow().get_index(idx).map(|(key, value)| (key.clone(), value.clone()))
+  }
+
+  pub fn ptr_eq(&self, other: &Map) -> bool {
+    Rc::ptr_eq(&self.0, &other.0)
+  }
+
+  // See `Array::addr`
+  pub(crate) fn addr(&self) -> usize {
+    Rc::as_ptr(&self.0) as usize
+  }
+
+  // Empties the map if this is the last handle to it, returning its values
+  pub(crate) fn take_if_last(&self) -> Option<Vec<Value>> {
+    (Rc::strong_count(&self.0) == 1).then(|| std::mem::take(&mut *self.0.borrow_mut()).into_values().collect())
+  }
+
+  // A new map holding the same entries. Nested collections are still shared.
+  pub fn snapshot(&self) -> Map {
+    Map::new(self.0.borrow().clone())
+  }
+
+  // See `Array::make_unique`
+  pub fn make_unique(&mut self) {
+    if Rc::strong_count(&self.0) > 1 {
+      *self = self.snapshot();
+    }
+  }
+
+  // Formats the entries, writing `{...}` instead for a map that contains itself
+  pub fn fmt_entries(&self, f: &mut fmt::Formatter, item: impl Fn(&Value, &mut fmt::Formatter) -> fmt::Result) -> fmt::Result {
+    format_once(self.addr(), f, "{...}", |f| {
+      write!(f, "{{")?;
+      for (i, (key, value)) in self.borrow().iter().enumerate() {
+        if i > 0 {
+          write!(f, ", ")?;
+        }
+        item(&key.to_value(), f)?;
+        write!(f, ": ")?;
+        item(value, f)?;
+      }
+      write!(f, "}}")
+    })
+  }
+}
+
+// See `Array`'s Drop
+impl Drop for Map {
+  fn drop(&mut self) {
+    if let Some(values) = self.take_if_last() {
+      drop_contents(values);
+    }
+  }
+}
+
+impl fmt::Debug for Map {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    self.fmt_entries(f, |value, f| write!(f, "{:?}", value))
+  }
+}
+
+// Maps are equal when they have the same keys, in any order, with values that are `==`
+impl PartialEq for Map {
+  fn eq(&self, other: &Map) -> bool {
+    compare_once((self.addr(), other.addr()), || {
+      let (entries, other_entries) = (self.borrow(), other.borrow());
+      entries.len() == other_entries.len() && entries.iter().all(|(key, value)| {
+        other_entries.get(key).is_some_and(|other_value| Interpreter::values_equal(value, other_value))
+      })
+    })
+  }
+}
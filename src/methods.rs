@@ -0,0 +1,159 @@
+// Methods on asa's own values, e.g. `a.push(1)` or `name.upper()`. A method that changes an
+// array or map changes it in place; under value semantics the interpreter hands it a copy first and
+// writes that back to wherever the receiver came from.
+
+use crate::array::*;
+use crate::error::*;
+use crate::interpreter::*;
+use crate::map::*;
+
+pub fn call_method(receiver: &Value, name: &str, args: &[Value]) -> Result<Value, AsaErrorKind> {
+  match receiver {
+    Value::Array(arr) => array_method(arr, name, args),
+    Value::Map(map) => map_method(map, name, args),
+    Value::String(s) => string_method(s, name, args),
+    // Host objects are shared, they change in place
+    Value::HostObject(object) => object.borrow_mut().call_method(name, args),
+    other => Err(no_method(other.type_name(), name)),
+  }
+}
+
+// Whether calling the method changes its receiver
+pub fn changes_receiver(receiver: &Value, name: &str) -> bool {
+  matches!(
+    (receiver, name),
+    (Value::Array(_), "push" | "pop" | "insert" | "prepend") | (Value::Map(_), "remove")
+  )
+}
+
+fn no_method(type_name: &str, name: &str) -> AsaErrorKind {
+  AsaErrorKind::UnknownMethod { type_name: type_name.to_string(), method: name.to_string() }
+}
+
+fn expect_args(name: &str, args: &[Value], count: usize) -> Result<(), AsaErrorKind> {
+  if args.len() == count {
+    return Ok(());
+  }
+  let expected = match count {
+    0 => "no arguments",
+    1 => "exactly one argument",
+    _ => "exactly two arguments",
+  };
+  Err(AsaErrorKind::WrongArgumentCount(format!("{} expects {}", name, expected)))
+}
+
+fn string_arg<'a>(name: &str, arg: &'a Value) -> Result<&'a str, AsaErrorKind> {
+  match arg {
+    Value::String(s) => Ok(s),
+    _ => Err(AsaErrorKind::TypeMismatch(format!("{} expects a string", name))),
+  }
+}
+
+fn array_method(arr: &Array, name: &str, args: &[Value]) -> Result<Value, AsaErrorKind> {
+  match name {
+    // push, insert and prepend evaluate to the changed array
+    "push" => {
+      expect_args(name, args, 1)?;
+      arr.borrow_mut().push(args[0].clone());
+      Ok(Value::Array(arr.clone()))
+    }
+    "pop" => {
+      expect_args(name, args, 0)?;
+      let popped = arr.borrow_mut().pop();
+      popped.ok_or(AsaErrorKind::PopOnEmptyArray)
+    }
+    "insert" => {
+      expect_args(name, args, 2)?;
+      let idx = match args[0] {
+        Value::Number(n) => n,
+        _ => return Err(AsaErrorKind::TypeMismatch("Index must be a number".to_string())),
+      };
+      if idx < 0 || (idx as usize) > arr.len() {
+        return Err(AsaErrorKind::IndexOutOfRange { index: idx, len: arr.len(), place: None });
+      }
+      arr.borrow_mut().insert(idx as usize, args[1].clone());
+      Ok(Value::Array(arr.clone()))
+    }
+    "prepend" => {
+      expect_args(name, args, 1)?;
+      arr.borrow_mut().insert(0, args[0].clone());
+      Ok(Value::Array(arr.clone()))
+    }
+    "contains" => {
+      expect_args(name, args, 1)?;
+      let found = arr.borrow().iter().any(|item| Interpreter::values_equal(item, &args[0]));
+      Ok(Value::Bool(found))
+    }
+    "join" => {
+      expect_args(name, args, 1)?;
+      let separator = string_arg(name, &args[0])?;
+      let items: Vec<String> = arr.borrow().iter().map(|item| item.to_string()).collect();
+      Ok(Value::String(items.join(separator).into()))
+    }
+    // A copy of the array and everything nested in it, like `copy(arr)`
+    "copy" => {
+      expect_args(name, args, 0)?;
+      Ok(Value::Array(arr.clone()).deep_copy())
+    }
+    _ => Err(no_method("array", name)),
+  }
+}
+
+fn map_method(map: &Map, name: &str, args: &[Value]) -> Result<Value, AsaErrorKind> {
+  match name {
+    // keys and values come in insertion order
+    "keys" => {
+      expect_args(name, args, 0)?;
+      Ok(Value::array(map.borrow().keys().map(MapKey::to_value).collect()))
+    }
+    "values" => {
+      expect_args(name, args, 0)?;
+      Ok(Value::array(map.borrow().values().cloned().collect()))
+    }
+    "has" => {
+      expect_args(name, args, 1)?;
+      let key = MapKey::from_value(&args[0])?;
+      Ok(Value::Bool(map.borrow().contains_key(&key)))
+    }
+    // Evaluates to the removed value; later entries keep their order
+    "remove" => {
+      expect_args(name, args, 1)?;
+      let key = MapKey::from_value(&args[0])?;
+      let removed = map.borrow_mut().shift_remove(&key);
+      removed.ok_or_else(|| AsaErrorKind::KeyNotFound(key.to_value().repr()))
+    }
+    "copy" => {
+      expect_args(name, args, 0)?;
+      Ok(Value::Map(map.clone()).deep_copy())
+    }
+    _ => Err(no_method("map", name)),
+  }
+}
+
+fn string_method(s: &str, name: &str, args: &[Value]) -> Result<Value, AsaErrorKind> {
+  let result = match name {
+    "upper" => {
+      expect_args(name, args, 0)?;
+      Value::String(s.to_uppercase().into())
+    }
+    "lower" => {
+      expect_args(name, args, 0)?;
+      Value::String(s.to_lowercase().into())
+    }
+    "trim" => {
+      expect_args(name, args, 0)?;
+      Value::String(s.trim().into())
+    }
+    "contains" => {
+      expect_args(name, args, 1)?;
+      Value::Bool(s.contains(string_arg(name, &args[0])?))
+    }
+    "split" => {
+      expect_args(name, args, 1)?;
+      let parts = s.split(string_arg(name, &args[0])?).map(|part| Value::String(part.into())).collect();
+      Value::array(parts)
+    }
+    _ => return Err(no_method("string", name)),
+  };
+  Ok(result)
+}
@@ -0,0 +1,40 @@
+// Where `print` and `eprint` send their output. By default that's the process's stdout and
+// stderr, but an embedder can hand the interpreter any `Write`, or capture into an OutputBuffer.
+
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+// A cloneable in-memory sink: give one clone to the interpreter and read the other
+#[derive(Debug, Clone, Default)]
+pub struct OutputBuffer {
+  bytes: Rc<RefCell<Vec<u8>>>,
+}
+
+impl OutputBuffer {
+  pub fn new() -> OutputBuffer {
+    OutputBuffer::default()
+  }
+
+  // Everything written so far, with invalid UTF-8 replaced
+  pub fn contents(&self) -> String {
+    String::from_utf8_lossy(&self.bytes.borrow()).to_string()
+  }
+
+  // Returns everything written so far and empties the buffer
+  pub fn take(&self) -> String {
+    let bytes = std::mem::take(&mut *self.bytes.borrow_mut());
+    String::from_utf8_lossy(&bytes).to_string()
+  }
+}
+
+impl Write for OutputBuffer {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    self.bytes.borrow_mut().extend_from_slice(buf);
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    Ok(())
+  }
+}
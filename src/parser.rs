@@ -6,12 +6,15 @@ use nom::*;
 use nom::{
   IResult,
   branch::alt,
-  combinator::{opt, map},
+  combinator::{opt, map, map_res},
   multi::{many1, many0, separated_list0},
   error::{ErrorKind},
   sequence::{tuple, terminated, delimited}
 };
 use crate::lexer::*;
+use crate::error::*;
+use crate::interpreter::{FunctionDef, Interpreter};
+use std::sync::Arc;
 
 // Here are the different node types. You will use these to make your parser.
 // You may add other nodes as you see fit.
@@ -20,25 +23,39 @@ use crate::lexer::*;
 pub enum Node {
   Program { children: Vec<Node> },
   Block { children: Vec<Node> },
-  Statement { children: Vec<Node> },
+  Statement { span: Span, children: Vec<Node> },
   FunctionDefine {name: Vec<u8>, children: Vec<Node> },
+  // Built into a definition as it's parsed, so evaluating the expression doesn't copy the body
+  FunctionExpression { def: Arc<FunctionDef> },
   FunctionArguments { children: Vec<Node> },
   FunctionStatements { children: Vec<Node> },
   IfExpression { children: Vec<Node> },
   WhileLoop { children: Vec<Node> },
+  ForLoop { children: Vec<Node> },
+  Range { inclusive: bool, children: Vec<Node> },
+  Match { children: Vec<Node> },
+  MatchArm { children: Vec<Node> },
+  PatternAlternatives { children: Vec<Node> },
+  ArrayPattern { children: Vec<Node> },
+  Wildcard,
   Expression { children: Vec<Node> },
-  FunctionCall { name: Vec<u8>, children: Vec<Node> },
+  FunctionCall { name: Vec<u8>, span: Span, children: Vec<Node> },
+  Call { span: Span, children: Vec<Node> },
   VariableDefine { children: Vec<Node> },
   ArgumentDefine { children: Vec<Node> },
   Assignment { children: Vec<Node> },
+  CompoundAssignment { name: Vec<u8>, children: Vec<Node> },
   FunctionReturn { children: Vec<Node> },
   UnaryExpression { name: Vec<u8>, children: Vec<Node> },
   BinaryExpression { name: Vec<u8>, children: Vec<Node> },
   Number { value: Vec<u8> },
+  Float { value: Vec<u8> },
+  Decimal { value: Vec<u8> },
   Bool { value: bool },
   Identifier { value: Vec<u8> },
   String { value: Vec<u8> },
   ArrayLiteral { children: Vec<Node> },
+  MapLiteral { children: Vec<Node> },
   IndexAccess { children: Vec<Node> },
   PropertyAccess { children: Vec<Node> },
   MethodCall { name: Vec<u8>, children: Vec<Node>},
@@ -50,19 +67,22 @@ pub enum Node {
 // Some helper functions to use Tokens instead of a &str with Nom.
 // You'll probably have to create more of these as needed.
 
+// The span of the tokens a parser consumed, given its input before and after parsing
+pub fn consumed_span(before: &Tokens, after: &Tokens) -> Span {
+  let consumed = before.len() - after.len();
+  if consumed == 0 {
+    return before.tokens.first().map(|tk| tk.span()).unwrap_or_default();
+  }
+  before.tokens[0].span().to(before.tokens[consumed - 1].span())
+}
+
 pub fn t_alpha(input: Tokens) -> IResult<Tokens, Token> {
-  let fxn = check_token(& |tk| match tk.kind {
-    TokenKind::Alpha => true,
-    _ => false,
-  });
+  let fxn = check_token(& |tk| matches!(tk.kind, TokenKind::Alpha));
   fxn(input.clone())
 }
 
 pub fn t_digit(input: Tokens) -> IResult<Tokens, Token> {
-  let fxn = check_token(& |tk| match tk.kind {
-    TokenKind::Digit => true,
-    _ => false,
-  });
+  let fxn = check_token(& |tk| matches!(tk.kind, TokenKind::Digit));
   fxn(input.clone())
 }
 
@@ -85,10 +105,7 @@ pub fn t_alphanumeric0(input: Tokens) -> IResult<Tokens, Vec<Token>> {
 // keywords
 
 pub fn t_let(input: Tokens) -> IResult<Tokens, Token> {
-  let fxn = check_token(& |tk| match tk.kind {
-    TokenKind::Let => true,
-    _ => false,
-  });
+  let fxn = check_token(& |tk| matches!(tk.kind, TokenKind::Let));
   fxn(input.clone())
 }
 
@@ -103,14 +120,43 @@ pub fn identifier(input: Tokens) -> IResult<Tokens, Node> {
   Ok((input,Node::Identifier{value: identifier}))
 }
 
-// number = {digit} ;
+// number = {digit} , [ "." , {digit} ] , [ "d" ] ;
 pub fn number(input: Tokens) -> IResult<Tokens, Node> {
   let (input, digits) = many1(t_digit)(input)?;
+  let mut last = digits[digits.len() - 1].clone();
   let mut number = Vec::new();
   for token in digits {
     number.extend(&token.lexeme);
   }
-  Ok((input, Node::Number { value: number }))
+
+  // A fractional part turns the literal into a float
+  let (input, fraction) = opt(tuple((
+    check_token(&|tk| tk.kind == TokenKind::Dot),
+    many1(t_digit),
+  )))(input)?;
+
+  let is_float = fraction.is_some();
+  if let Some((_, fraction_digits)) = fraction {
+    number.push(b'.');
+    last = fraction_digits[fraction_digits.len() - 1].clone();
+    for token in fraction_digits {
+      number.extend(&token.lexeme);
+    }
+  }
+
+  // A `d` written directly after the digits makes an exact decimal, e.g. `12.50d`
+  let (input, suffix) = opt(check_token(&|tk| {
+    tk.kind == TokenKind::Alpha && tk.lexeme == b"d" &&
+      tk.start_line == last.end_line && tk.start_col == last.end_col + 1
+  }))(input)?;
+
+  if suffix.is_some() {
+    Ok((input, Node::Decimal { value: number }))
+  } else if is_float {
+    Ok((input, Node::Float { value: number }))
+  } else {
+    Ok((input, Node::Number { value: number }))
+  }
 }
 
 // boolean = "true" | "false" ;
@@ -143,6 +189,7 @@ pub fn call_arguments(input: Tokens) -> IResult<Tokens, Node> {
 
 // function_call = identifier , "(" , [arguments] , ")" ;
 pub fn function_call(input: Tokens) -> IResult<Tokens, Node> {
+  let start = input.clone();
   let (input, (name_node, _, args_opt, _)) = tuple((
     identifier,
     check_token(&|tk| tk.kind == TokenKind::LeftParen),
@@ -167,7 +214,8 @@ pub fn function_call(input: Tokens) -> IResult<Tokens, Node> {
     },
   };
 
-  Ok((input, Node::FunctionCall { name, children: vec![function_arguments] }))
+  let span = consumed_span(&start, &input);
+  Ok((input, Node::FunctionCall { name, span, children: vec![function_arguments] }))
 }
 
 // value = number | identifier | boolean ;
@@ -180,6 +228,7 @@ pub fn expression(input: Tokens) -> IResult<Tokens, Node> {
   map(
     alt((
       if_expression,
+      match_expression,
       logical_or,
       boolean,
       function_call,
@@ -340,6 +389,144 @@ pub fn if_expression(input: Tokens) -> IResult<Tokens, Node> {
   Ok((input, Node::IfExpression { children }))
 }
 
+// match_expression = "match" , expression , "{" , { match_arm , [ "," ] } , "}" ;
+pub fn match_expression(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, _) = check_token(&|tk| tk.kind == TokenKind::Match)(input)?;
+  let (input, value) = expression(input)?;
+  let (input, _) = check_token(&|tk| tk.kind == TokenKind::LeftCurly)(input)?;
+  let (input, arms) = many0(terminated(
+    match_arm,
+    opt(check_token(&|tk| tk.kind == TokenKind::Comma)),
+  ))(input)?;
+  let (input, _) = check_token(&|tk| tk.kind == TokenKind::RightCurly)(input)?;
+
+  let mut children = vec![value];
+  children.extend(arms);
+  Ok((input, Node::Match { children }))
+}
+
+// match_arm = pattern , [ "if" , expression ] , "=>" , ( "{" , <statements> , "}" | expression ) ;
+// The node's children are the pattern, the guard (Null when there's none) and the body.
+pub fn match_arm(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, pattern) = pattern(input)?;
+  let (input, guard) = opt(tuple((
+    check_token(&|tk| tk.kind == TokenKind::If),
+    expression,
+  )))(input)?;
+  let (input, _) = check_token(&|tk| tk.kind == TokenKind::FatArrow)(input)?;
+  let (input, body) = alt((
+    delimited(
+      check_token(&|tk| tk.kind == TokenKind::LeftCurly),
+      block,
+      check_token(&|tk| tk.kind == TokenKind::RightCurly),
+    ),
+    expression,
+  ))(input)?;
+
+  let guard = guard.map(|(_, guard)| guard).unwrap_or(Node::Null);
+  Ok((input, Node::MatchArm { children: vec![pattern, guard, body] }))
+}
+
+// pattern = single_pattern , { "|" , single_pattern } ;
+pub fn pattern(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, first) = single_pattern(input)?;
+  let (input, rest) = many0(tuple((
+    check_token(&|tk| tk.kind == TokenKind::Pipe),
+    single_pattern,
+  )))(input)?;
+  if rest.is_empty() {
+    return Ok((input, first));
+  }
+  let mut children = vec![first];
+  children.extend(rest.into_iter().map(|(_, alternative)| alternative));
+  Ok((input, Node::PatternAlternatives { children }))
+}
+
+// single_pattern = "_" | range_pattern | literal_pattern | array_pattern | identifier ;
+// Literals are matched by equality, and an identifier matches anything and binds it.
+pub fn single_pattern(input: Tokens) -> IResult<Tokens, Node> {
+  alt((
+    map(check_token(&|tk| tk.kind == TokenKind::Underscore), |_| Node::Wildcard),
+    range_pattern,
+    literal_pattern,
+    array_pattern,
+    identifier,
+  ))(input)
+}
+
+// range_pattern = literal_pattern , ( ".." | "..=" ) , literal_pattern ;
+pub fn range_pattern(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, start) = literal_pattern(input)?;
+  let (input, _) = check_token(&|tk| tk.kind == TokenKind::Dot)(input)?;
+  let (input, _) = check_token(&|tk| tk.kind == TokenKind::Dot)(input)?;
+  let (input, inclusive) = opt(check_token(&|tk| tk.kind == TokenKind::Equal))(input)?;
+  let (input, end) = literal_pattern(input)?;
+  Ok((input, Node::Range { inclusive: inclusive.is_some(), children: vec![start, end] }))
+}
+
+// literal_pattern = [ "-" ] , number | string | boolean ;
+pub fn literal_pattern(input: Tokens) -> IResult<Tokens, Node> {
+  alt((
+    map(
+      tuple((check_token(&|tk| tk.kind == TokenKind::Dash), number)),
+      |(_, n)| Node::UnaryExpression { name: b"-".to_vec(), children: vec![n] },
+    ),
+    number,
+    string,
+    boolean,
+  ))(input)
+}
+
+// array_pattern = "[" , [ pattern , { "," , pattern } ] , "]" ;
+pub fn array_pattern(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, _) = check_token(&|tk| tk.kind == TokenKind::LeftBracket)(input)?;
+  let (input, children) = separated_list0(check_token(&|tk| tk.kind == TokenKind::Comma), pattern)(input)?;
+  let (input, _) = check_token(&|tk| tk.kind == TokenKind::RightBracket)(input)?;
+  Ok((input, Node::ArrayPattern { children }))
+}
+
+// for_loop = "for" , identifier , [ "," , identifier ] , "in" , ( range | expression ) , "{" , <statements> , "}" ;
+// The node's children are the index variable (Null when there's none), the value variable, what
+// is iterated over, and the body.
+pub fn for_loop(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, _) = check_token(&|tk| tk.kind == TokenKind::For)(input)?;
+  let (input, first) = identifier(input)?;
+  let (input, second) = opt(tuple((
+    check_token(&|tk| tk.kind == TokenKind::Comma),
+    identifier,
+  )))(input)?;
+  let (index, value) = match second {
+    Some((_, value)) => (first, value),
+    None => (Node::Null, first),
+  };
+  let (input, _) = check_token(&|tk| tk.kind == TokenKind::In)(input)?;
+  let (input, iterable) = alt((range, expression))(input)?;
+  let (input, _) = check_token(&|tk| tk.kind == TokenKind::LeftCurly)(input)?;
+  let (input, body_stmts) = block(input)?;
+  let (input, _) = check_token(&|tk| tk.kind == TokenKind::RightCurly)(input)?;
+
+  Ok((input, Node::ForLoop {
+    children: vec![index, value, iterable, body_stmts]
+  }))
+}
+
+// range = expression , ( ".." | "..=" ) , expression , [ "step" , expression ] ;
+pub fn range(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, start) = expression(input)?;
+  let (input, _) = check_token(&|tk| tk.kind == TokenKind::Dot)(input)?;
+  let (input, _) = check_token(&|tk| tk.kind == TokenKind::Dot)(input)?;
+  let (input, inclusive) = opt(check_token(&|tk| tk.kind == TokenKind::Equal))(input)?;
+  let (input, end) = expression(input)?;
+  let (input, step) = opt(tuple((
+    check_token(&|tk| tk.kind == TokenKind::Step),
+    expression,
+  )))(input)?;
+
+  let mut children = vec![start, end];
+  children.extend(step.map(|(_, step)| step));
+  Ok((input, Node::Range { inclusive: inclusive.is_some(), children }))
+}
+
 // while_loop = "while" , "(" , expression , ")" , "{" , <statements> , "}" ;
 pub fn while_loop(input: Tokens) -> IResult<Tokens, Node> {
   let (input, _) = check_token(&|tk| tk.kind == TokenKind::While)(input)?;
@@ -381,8 +568,39 @@ pub fn array_literal(input: Tokens) -> IResult<Tokens, Node> {
   Ok((input, Node::ArrayLiteral{ children: elements }))
 }
 
-// postfix = primary { ("." identifier) | ("[" expression "]") }
+// map_literal = "{" , [ map_entry , { "," , map_entry } , [ "," ] ] , "}" ;
+// Children alternate between keys and values
+pub fn map_literal(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, _) = check_token(&|tk| tk.kind == TokenKind::LeftCurly)(input)?;
+  let (input, entries) = separated_list0(check_token(&|tk| tk.kind == TokenKind::Comma), map_entry)(input)?;
+  let (input, _) = if entries.is_empty() {
+    (input, None)
+  } else {
+    opt(check_token(&|tk| tk.kind == TokenKind::Comma))(input)?
+  };
+  let (input, _) = check_token(&|tk| tk.kind == TokenKind::RightCurly)(input)?;
+  let children = entries.into_iter().flat_map(|(key, value)| [key, value]).collect();
+  Ok((input, Node::MapLiteral { children }))
+}
+
+// map_entry = ( identifier | literal_pattern ) , ":" , expression ;
+// A bare identifier key is the string of its name, as in `{ b: 2 }`
+pub fn map_entry(input: Tokens) -> IResult<Tokens, (Node, Node)> {
+  let (input, key) = alt((
+    map(identifier, |node| match node {
+      Node::Identifier { value } => Node::String { value },
+      _ => unreachable!(),
+    }),
+    literal_pattern,
+  ))(input)?;
+  let (input, _) = check_token(&|tk| tk.kind == TokenKind::Colon)(input)?;
+  let (input, value) = expression(input)?;
+  Ok((input, (key, value)))
+}
+
+// postfix = primary { ("." identifier) | ("[" expression "]") | ("(" [call_arguments] ")") }
 pub fn postfix(input: Tokens) -> IResult<Tokens, Node> {
+  let start = input.clone();
   let (input, mut node) = primary(input)?;
 
   let mut current_input = input;
@@ -406,54 +624,47 @@ pub fn postfix(input: Tokens) -> IResult<Tokens, Node> {
         |(_, idx_expr, _)| {
           Node::IndexAccess { children: vec![node.clone(), idx_expr] }
         }
-      )
+      ),
+
+      // Parse (arguments), calling whatever the expression so far produces
+      map(
+        tuple((
+          check_token(&|tk| tk.kind == TokenKind::LeftParen),
+          call_arguments,
+          check_token(&|tk| tk.kind == TokenKind::RightParen),
+        )),
+        |(_, args, _)| call_node(node.clone(), args)
+      ),
     ))(current_input.clone());
 
     match res {
       Ok((next_input, new_node)) => {
-        node = new_node;
+        // Calls remember the whole callee and argument list for tracebacks
+        node = match new_node {
+          Node::Call { children, .. } => Node::Call { span: consumed_span(&start, &next_input), children },
+          other => other,
+        };
         current_input = next_input;
       },
       Err(_) => { break; }
     }
   }
 
-  let (current_input, maybe_method_call) = opt(tuple((
-    check_token(&|tk| tk.kind == TokenKind::LeftParen),
-    separated_list0(check_token(&|tk| tk.kind == TokenKind::Comma), expression),
-    check_token(&|tk| tk.kind == TokenKind::RightParen),
-  )))(current_input)?;
-
-  if let Some((_, args, _)) = maybe_method_call {
-    // If node is a PropertyAccess, it should have children:
-    // children[0] = object, children[1] = method identifier.
-    if let Node::PropertyAccess { children } = node {
-      let object_node = children[0].clone();
-
-      // The method name should come from children[1] if it's an Identifier node
-      let method_name = if let Node::Identifier { value } = &children[1] {
-        value.clone()
-      } else {
-        return Err(Err::Error(nom::error::Error::new(current_input, nom::error::ErrorKind::Tag)));
-      };
-
-      // Construct the new children array:
+  Ok((current_input, node))
+}
+
+// `object.name(args)` calls a method on the object (falling back to a function stored in the
+// property, see `eval_method_call`), any other callee is called as a function value
+fn call_node(callee: Node, args: Node) -> Node {
+  if let (Node::PropertyAccess { children }, Node::FunctionArguments { children: args }) = (&callee, &args) {
+    if let Node::Identifier { value } = &children[1] {
       // first entry = object, then all arguments follow
-      let mut new_children = Vec::with_capacity(1 + args.len());
-      new_children.push(object_node);
-      new_children.extend(args);
-
-      node = Node::MethodCall {
-        name: method_name,
-        children: new_children,
-      };
-    } else {
-      // If we don't have a property access but found a '(', this might represent a
-      // normal function call scenario handled elsewhere. For now, do nothing here.
+      let mut new_children = vec![children[0].clone()];
+      new_children.extend(args.iter().cloned());
+      return Node::MethodCall { name: value.clone(), children: new_children };
     }
   }
-
-  Ok((current_input, node))
+  Node::Call { span: Span::default(), children: vec![callee, args] }
 }
 
 // addition = multiplication , { ("+" | "-", "%") , multiplication } ;
@@ -563,7 +774,8 @@ pub fn unary(input: Tokens) -> IResult<Tokens, Node> {
   }
 }
 
-// primary = number | identifier | boolean | string | function_call | "(" expression ")" | array_literal ;
+// primary = number | identifier | boolean | string | function_call | "(" expression ")" | array_literal
+//         | map_literal | function_expression ;
 pub fn primary(input: Tokens) -> IResult<Tokens, Node> {
   alt((
     map(
@@ -574,37 +786,48 @@ pub fn primary(input: Tokens) -> IResult<Tokens, Node> {
       )),
       |(_, expr_node, _)| expr_node,
     ),
+    function_expression,
     function_call,
     number,
     identifier,
     boolean,
     array_literal,
+    map_literal,
     string,
   ))(input)
 }
 
-// statement = variable_define , ";" | assignment , ";" | function_return , ";" | if_expression | while_loop ;
+// statement = variable_define , ";" | assignment , ";" | function_return , ";" | if_expression | while_loop
+//           | expression , ";" ;
 pub fn statement(input: Tokens) -> IResult<Tokens, Node> {
+  let start = input.clone();
   let (input, stmt_node) = alt((
     // if_expression,
     map(terminated(variable_define, check_token(&|tk| tk.kind == TokenKind::Semicolon)), |node| node),
     map(terminated(assignment, check_token(&|tk| tk.kind == TokenKind::Semicolon)), |node| node),
+    map(terminated(compound_assignment, check_token(&|tk| tk.kind == TokenKind::Semicolon)), |node| node),
     map(terminated(function_call, check_token(&|tk| tk.kind == TokenKind::Semicolon)), |node| node),
     map(terminated(function_return, check_token(&|tk| tk.kind == TokenKind::Semicolon)), |node| node),
     map(terminated(break_statement, check_token(&|tk| tk.kind == TokenKind::Semicolon)), |node| node),
     map(terminated(continue_statement, check_token(&|tk| tk.kind == TokenKind::Semicolon)), |node| node),
     if_expression,
-    while_loop
+    match_expression,
+    while_loop,
+    for_loop,
+    // Any other expression run for its effect, like `rows[i].push(x);`
+    map(terminated(expression, check_token(&|tk| tk.kind == TokenKind::Semicolon)), |node| node),
   ))(input)?;
 
+  // Statements remember where they came from so runtime errors can point at them
+  let span = consumed_span(&start, &input);
   let (input, _) = opt(comment)(input)?;
-  Ok((input, stmt_node))
+  Ok((input, Node::Statement { span, children: vec![stmt_node] }))
 }
 
-// function_return = "return" , (function_call | expression | identifier) ;
+// function_return = "return" , expression ;
 pub fn function_return(input: Tokens) -> IResult<Tokens, Node> {
   let (input, _) = check_token(&|tk| tk.kind == TokenKind::Return)(input)?;
-  let (input, ret_node) = alt((function_call, expression, identifier))(input)?;
+  let (input, ret_node) = expression(input)?;
   Ok((input, Node::FunctionReturn { children: vec![ret_node] }))
 }
 
@@ -641,6 +864,32 @@ pub fn assignment(input: Tokens) -> IResult<Tokens, Node> {
   Ok((input, Node::Assignment { children: vec![id_node, expr_node] }))
 }
 
+// compound_assignment = lvalue , ( "+=" | "-=" | "*=" | "/=" | "%=" | "^=" ) , expression
+//                     | lvalue , ( "++" | "--" ) ;
+// `name` is the operator applied, `x++` is written as `x += 1`
+pub fn compound_assignment(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, id_node) = lvalue(input)?;
+  let (input, op) = check_token(&|tk| matches!(tk.kind,
+    TokenKind::PlusEqual | TokenKind::DashEqual | TokenKind::MultiplyEqual | TokenKind::SlashEqual |
+    TokenKind::ModulusEqual | TokenKind::ExponentEqual | TokenKind::PlusPlus | TokenKind::DashDash
+  ))(input)?;
+  let name = match op.kind {
+    TokenKind::PlusEqual | TokenKind::PlusPlus => b"+".to_vec(),
+    TokenKind::DashEqual | TokenKind::DashDash => b"-".to_vec(),
+    TokenKind::MultiplyEqual => b"*".to_vec(),
+    TokenKind::SlashEqual => b"/".to_vec(),
+    TokenKind::ModulusEqual => b"%".to_vec(),
+    _ => b"^".to_vec(),
+  };
+  let (input, expr_node) = match op.kind {
+    TokenKind::PlusPlus | TokenKind::DashDash => {
+      (input, Node::Expression { children: vec![Node::Number { value: b"1".to_vec() }] })
+    }
+    _ => expression(input)?,
+  };
+  Ok((input, Node::CompoundAssignment { name, children: vec![id_node, expr_node] }))
+}
+
 // variable_define = "let" , identifier , "=" , expression ;
 pub fn variable_define(input: Tokens) -> IResult<Tokens, Node> {
   let (input, _) = t_let(input)?;
@@ -694,6 +943,13 @@ pub fn function_define(input: Tokens) -> IResult<Tokens, Node> {
   } else {
     return Err(Err::Error(nom::error::Error::new(input, ErrorKind::Tag)));
   };
+  let (input, children) = function_parts(input)?;
+  Ok((input, Node::FunctionDefine { name, children }))
+}
+
+// The parameters and body shared by named and anonymous functions:
+// "(" , [arguments] , ")" , "{" , <statement> , "}"
+fn function_parts(input: Tokens) -> IResult<Tokens, Vec<Node>> {
   let (input, _) = check_token(&|tk| tk.kind == TokenKind::LeftParen)(input)?;
   let (input, args_opt) = opt(arguments)(input)?;
   let args = if let Some(Node::FunctionArguments { children }) = args_opt {
@@ -706,13 +962,58 @@ pub fn function_define(input: Tokens) -> IResult<Tokens, Node> {
   let (input, stmts) = many0(statement)(input)?;
   let (input, _) = check_token(&|tk| tk.kind == TokenKind::RightCurly)(input)?;
 
-  Ok((input, Node::FunctionDefine {
-    name,
-    children: vec![
-      Node::FunctionArguments { children: args },
-      Node::FunctionStatements { children: stmts },
-    ]
-  }))
+  Ok((input, vec![
+    Node::FunctionArguments { children: args },
+    Node::FunctionStatements { children: stmts },
+  ]))
+}
+
+// function_expression = "fn" , "(" , [arguments] , ")" , "{" , <statement> , "}"
+//                     | "|" , [arguments] , "|" , expression
+//                     | "||" , expression ;
+pub fn function_expression(input: Tokens) -> IResult<Tokens, Node> {
+  alt((
+    map_res(
+      tuple((check_token(&|tk| tk.kind == TokenKind::Fn), function_parts)),
+      |(_, children)| anonymous_function(&children),
+    ),
+    short_function_expression,
+  ))(input)
+}
+
+fn anonymous_function(children: &[Node]) -> Result<Node, AsaErrorKind> {
+  let def = Interpreter::function_def(b"anonymous", children)?;
+  Ok(Node::FunctionExpression { def: Arc::new(def) })
+}
+
+// The `|x| x * 2` form, whose body is a single expression that is returned
+fn short_function_expression(input: Tokens) -> IResult<Tokens, Node> {
+  let (input, args) = alt((
+    map(check_token(&|tk| tk.kind == TokenKind::LogicalOr), |_| Vec::new()),
+    map(
+      tuple((
+        check_token(&|tk| tk.kind == TokenKind::Pipe),
+        arguments,
+        check_token(&|tk| tk.kind == TokenKind::Pipe),
+      )),
+      |(_, args, _)| match args {
+        Node::FunctionArguments { children } => children,
+        _ => Vec::new(),
+      },
+    ),
+  ))(input)?;
+  let start = input.clone();
+  let (input, body) = expression(input)?;
+  let span = consumed_span(&start, &input);
+  let ret = Node::Statement { span, children: vec![Node::FunctionReturn { children: vec![body] }] };
+
+  let children = [
+    Node::FunctionArguments { children: args },
+    Node::FunctionStatements { children: vec![ret] },
+  ];
+  let node = anonymous_function(&children)
+    .map_err(|_| Err::Error(nom::error::Error::new(input.clone(), ErrorKind::MapRes)))?;
+  Ok((input, node))
 }
 
 // comment = "//" , (?any-character? - newline);
@@ -738,6 +1039,23 @@ pub fn program(input: Tokens) -> IResult<Tokens, Node> {
   )(input)
 }
 
+// Lexes and parses a whole source file, reporting where it stopped making sense
+pub fn parse_source(source: &str) -> Result<Node, AsaError> {
+  let tokens = lex(source);
+  check_tokens(&tokens)?;
+  let (rest, tree) = program(tokens)
+    .map_err(|e| AsaError::new(AsaErrorKind::SyntaxError(format!("{:?}", e))))?;
+  if !rest.is_done() {
+    let token = &rest.tokens[0];
+    let found = match token.kind {
+      TokenKind::Alpha | TokenKind::Digit => format!("`{}`", token.lexeme[0] as char),
+      kind => format!("{:?}", kind),
+    };
+    return Err(AsaError::new(AsaErrorKind::SyntaxError(format!("unexpected {}", found))).at(token.span()));
+  }
+  Ok(tree)
+}
+
 // block = { statement | comment } ;
 pub fn block(input: Tokens) -> IResult<Tokens, Node> {
   map(
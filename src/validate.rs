@@ -0,0 +1,99 @@
+// Static checks run on a parsed program before it is interpreted.
+
+use crate::error::*;
+use crate::parser::Node;
+
+#[derive(Clone, Copy)]
+struct Context {
+  in_loop: bool,
+  can_return: bool,
+}
+
+// Rejects `break`/`continue` outside of a loop and `return` outside of a function. The error
+// points at the innermost statement holding the offending node.
+pub fn validate(node: &Node) -> Result<(), AsaError> {
+  check(node, Context { in_loop: false, can_return: false })
+}
+
+fn check(node: &Node, ctx: Context) -> Result<(), AsaError> {
+  match node {
+    Node::Break if !ctx.in_loop => Err(AsaErrorKind::BreakOutsideLoop.into()),
+    Node::Continue if !ctx.in_loop => Err(AsaErrorKind::ContinueOutsideLoop.into()),
+    Node::FunctionReturn { .. } if !ctx.can_return => Err(AsaErrorKind::ReturnOutsideFunction.into()),
+    Node::Statement { span, children } => check_all(children, ctx).map_err(|e| e.at(*span)),
+    // Default argument values are evaluated by the callee, the body starts outside of any loop
+    Node::FunctionDefine { children, .. } => check_all(children, Context { in_loop: false, can_return: true }),
+    Node::FunctionExpression { def } => {
+      let ctx = Context { in_loop: false, can_return: true };
+      for default in def.params.iter().filter_map(|(_, default)| default.as_ref()) {
+        check(default, ctx)?;
+      }
+      check(&def.body, ctx)
+    }
+    Node::WhileLoop { children } => {
+      check(&children[0], ctx)?;
+      check(&children[1], Context { in_loop: true, ..ctx })
+    }
+    Node::ForLoop { children } => {
+      check(&children[2], ctx)?;
+      check(&children[3], Context { in_loop: true, ..ctx })
+    }
+    Node::Expression { children } if matches!(children[0], Node::IfExpression { .. } | Node::Match { .. }) => {
+      // An if or match used as a value gets its value from `return`, and loops don't reach into it
+      check(&children[0], Context { in_loop: false, can_return: true })
+    }
+    _ => check_all(children(node), ctx),
+  }
+}
+
+fn check_all(nodes: &[Node], ctx: Context) -> Result<(), AsaError> {
+  for node in nodes {
+    check(node, ctx)?;
+  }
+  Ok(())
+}
+
+fn children(node: &Node) -> &[Node] {
+  match node {
+    Node::Program { children } |
+    Node::Block { children } |
+    Node::Statement { children, .. } |
+    Node::FunctionDefine { children, .. } |
+    Node::FunctionArguments { children } |
+    Node::FunctionStatements { children } |
+    Node::IfExpression { children } |
+    Node::WhileLoop { children } |
+    Node::ForLoop { children } |
+    Node::Range { children, .. } |
+    Node::Expression { children } |
+    Node::FunctionCall { children, .. } |
+    Node::Call { children, .. } |
+    Node::VariableDefine { children } |
+    Node::ArgumentDefine { children } |
+    Node::Assignment { children } |
+    Node::CompoundAssignment { children, .. } |
+    Node::FunctionReturn { children } |
+    Node::UnaryExpression { children, .. } |
+    Node::BinaryExpression { children, .. } |
+    Node::ArrayLiteral { children } |
+    Node::MapLiteral { children } |
+    Node::IndexAccess { children } |
+    Node::PropertyAccess { children } |
+    Node::MethodCall { children, .. } |
+    Node::Match { children } |
+    Node::MatchArm { children } |
+    Node::PatternAlternatives { children } |
+    Node::ArrayPattern { children } => children,
+    Node::Number { .. } |
+    Node::Float { .. } |
+    Node::Decimal { .. } |
+    Node::Bool { .. } |
+    Node::Identifier { .. } |
+    Node::String { .. } |
+    Node::FunctionExpression { .. } |
+    Node::Null |
+    Node::Wildcard |
+    Node::Break |
+    Node::Continue => &[],
+  }
+}
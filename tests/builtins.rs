@@ -0,0 +1,61 @@
+use asa::*;
+
+#[test]
+fn builtins_register_and_call() {
+  let mut engine = Engine::new();
+  engine.register_fn("double", Arity::Exact(1), |_, args| match args[0] {
+    Value::Number(n) => Ok(Value::Number(n * 2)),
+    _ => Err(AsaErrorKind::TypeMismatch("double expects a number".to_string())),
+  });
+  assert_eq!(engine.eval("double(21)"), Ok(Value::Number(42)));
+  assert_eq!(engine.call("double", &[Value::Number(4)]), Ok(Value::Number(8)));
+}
+
+#[test]
+fn builtins_arity_is_checked() {
+  let mut engine = Engine::new();
+  engine.register_fn("pair", Arity::Range(1, 2), |_, args| Ok(Value::array(args.to_vec())));
+  assert_eq!(engine.eval("pair(1)"), Ok(Value::array(vec![Value::Number(1)])));
+  assert_eq!(
+    engine.eval("pair(1, 2, 3)").unwrap_err().kind,
+    AsaErrorKind::WrongArgumentCount("pair expects 1..2 arguments, got 3".to_string())
+  );
+  assert_eq!(
+    engine.eval(r#"len("a", "b")"#).unwrap_err().kind,
+    AsaErrorKind::WrongArgumentCount("len expects 1 argument, got 2".to_string())
+  );
+}
+
+#[test]
+fn builtins_are_first_class() {
+  let mut engine = Engine::new();
+  let result = engine.eval(r#"
+fn apply(f, x) {
+    return f(x);
+}
+let fs = [len, apply];
+let first = fs[0];
+apply(first, "abc") + first([1, 2])
+"#);
+  assert_eq!(result, Ok(Value::Number(5)));
+  assert_eq!(engine.eval(r#""" + len"#), Ok(Value::String("<native fn len/1>".into())));
+}
+
+#[test]
+fn builtins_can_be_shadowed() {
+  let mut engine = Engine::new();
+  engine.load("fn len(x) { return 0; }").unwrap();
+  assert_eq!(engine.eval("len([1, 2, 3])"), Ok(Value::Number(0)));
+}
+
+#[test]
+fn builtins_receive_interpreter() {
+  let mut engine = Engine::new();
+  let stdout = engine.interpreter().capture_stdout();
+  engine.register_fn("shout", Arity::Exact(1), |interp, args| {
+    writeln!(interp.stdout(), "{}!", args[0]).unwrap();
+    Ok(Value::Bool(true))
+  });
+  engine.eval(r#"shout("hey");"#).unwrap();
+  assert_eq!(stdout.contents(), "hey!\n");
+}
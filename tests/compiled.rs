@@ -0,0 +1,69 @@
+use std::sync::Arc;
+use std::thread;
+
+use asa::*;
+
+const RULES: &str = r#"
+let threshold = 100;
+fn discount(total) {
+    if total > threshold {
+        return total / 10;
+    }
+    return 0;
+}
+"#;
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn compiled_is_send_and_sync() {
+  assert_send_sync::<CompiledProgram>();
+}
+
+#[test]
+fn compiled_instantiate_and_call() {
+  let program = CompiledProgram::compile(RULES).unwrap();
+  let mut engine = Engine::from_program(&program).unwrap();
+  assert_eq!(engine.call("discount", &[Value::Number(250)]), Ok(Value::Number(25)));
+  assert_eq!(engine.call("discount", &[Value::Number(50)]), Ok(Value::Number(0)));
+}
+
+#[test]
+fn compiled_instances_have_their_own_globals() {
+  let program = CompiledProgram::compile(RULES).unwrap();
+  let mut first = Engine::from_program(&program).unwrap();
+  let mut second = Engine::from_program(&program).unwrap();
+  first.eval("threshold = 200;").unwrap();
+  assert_eq!(first.get_global("threshold"), Some(Value::Number(200)));
+  assert_eq!(second.get_global("threshold"), Some(Value::Number(100)));
+  assert_eq!(first.call("discount", &[Value::Number(150)]), Ok(Value::Number(0)));
+  assert_eq!(second.call("discount", &[Value::Number(150)]), Ok(Value::Number(15)));
+}
+
+#[test]
+fn compiled_run_returns_last_value() {
+  let program = CompiledProgram::compile("fn square(x) { return x * x; } let y = square(4); y + 1").unwrap();
+  let mut interpreter = Interpreter::new();
+  assert_eq!(program.run(&mut interpreter), Ok(Value::Number(17)));
+}
+
+#[test]
+fn compiled_reports_errors_at_compile_time() {
+  assert_eq!(CompiledProgram::compile("let a = $;").unwrap_err().kind.code(), "E0001");
+  assert_eq!(CompiledProgram::compile("break;").unwrap_err().kind, AsaErrorKind::BreakOutsideLoop);
+}
+
+#[test]
+fn compiled_shared_across_threads() {
+  let program = Arc::new(CompiledProgram::compile(RULES).unwrap());
+  let handles: Vec<_> = (1..=4).map(|i| {
+    let program = Arc::clone(&program);
+    thread::spawn(move || {
+      let mut engine = Engine::from_program(&program).unwrap();
+      // Values can hold Rc-based host data, so convert before leaving the thread
+      i32::from_value(&engine.call("discount", &[Value::Number(i * 1000)]).unwrap()).unwrap()
+    })
+  }).collect();
+  let results: Vec<i32> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+  assert_eq!(results, vec![100, 200, 300, 400]);
+}
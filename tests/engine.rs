@@ -0,0 +1,151 @@
+use asa::*;
+
+#[test]
+fn engine_eval_returns_last_value() {
+  let mut engine = Engine::new();
+  assert_eq!(engine.eval("let x = 2; x * 21"), Ok(Value::Number(42)));
+}
+
+#[test]
+fn engine_definitions_persist_between_evals() {
+  let mut engine = Engine::new();
+  engine.load("let base = 10; fn addBase(n) { return n + base; }").unwrap();
+  assert_eq!(engine.eval("addBase(5)"), Ok(Value::Number(15)));
+}
+
+#[test]
+fn engine_call_with_values() {
+  let mut engine = Engine::new();
+  engine.load(r#"fn greet(name, greeting = "Hello") { return greeting + ", " + name; }"#).unwrap();
+  assert_eq!(
+    engine.call("greet", &[Value::String("asa".into())]),
+    Ok(Value::String("Hello, asa".into()))
+  );
+  assert_eq!(
+    engine.call("greet", &[Value::String("asa".into()), Value::String("Hi".into())]),
+    Ok(Value::String("Hi, asa".into()))
+  );
+}
+
+#[test]
+fn engine_call_undefined() {
+  let mut engine = Engine::new();
+  assert_eq!(engine.call("missing", &[]).unwrap_err().kind, AsaErrorKind::UndefinedFunction);
+}
+
+#[test]
+fn engine_globals() {
+  let mut engine = Engine::new();
+  engine.set_global("limit", Value::Number(3));
+  engine.load("fn underLimit(n) { return n < limit; }").unwrap();
+  assert_eq!(engine.call("underLimit", &[Value::Number(2)]), Ok(Value::Bool(true)));
+  engine.eval("limit = limit - 2;").unwrap();
+  assert_eq!(engine.get_global("limit"), Some(Value::Number(1)));
+  assert_eq!(engine.get_global("missing"), None);
+}
+
+#[test]
+fn engine_reports_errors() {
+  let mut engine = Engine::new();
+  assert_eq!(engine.eval("let a = $;").unwrap_err().kind.code(), "E0001");
+  assert_eq!(engine.eval("break;").unwrap_err().kind, AsaErrorKind::BreakOutsideLoop);
+  assert_eq!(engine.eval("[1][4]").unwrap_err().kind, AsaErrorKind::IndexOutOfRange { index: 4, len: 1, place: None });
+}
+
+#[test]
+fn engine_reference_semantics_share_arrays() {
+  // Arrays are shared references unless the host asks for value semantics
+  let mut engine = Engine::new();
+  engine.load("fn grow(tape) { tape.push(0); tape[0] = 9; } let tape = [1]; let alias = tape; grow(alias);").unwrap();
+  let grown = Value::array(vec![Value::Number(9), Value::Number(0)]);
+  assert_eq!(engine.get_global("tape"), Some(grown.clone()));
+  assert_eq!(engine.eval("[same(tape, alias), same(tape, [9, 0]), tape == [9, 0]]"), Ok(Value::array(vec![
+    Value::Bool(true),
+    Value::Bool(false),
+    Value::Bool(true),
+  ])));
+}
+
+#[test]
+fn engine_value_semantics_copy_on_change() {
+  let mut engine = Engine::new();
+  engine.set_array_semantics(ArraySemantics::Value);
+  engine.load("fn grow(tape) { tape.push(0); } let grid = [[0, 0]]; let row = grid[0]; row[1] = 5; grow(row);").unwrap();
+  assert_eq!(engine.eval("grid"), Ok(Value::array(vec![Value::array(vec![Value::Number(0), Value::Number(0)])])));
+  assert_eq!(engine.eval("[row, same(grid[0], [0, 0])]"), Ok(Value::array(vec![
+    Value::array(vec![Value::Number(0), Value::Number(5)]),
+    Value::Bool(true),
+  ])));
+}
+
+#[test]
+fn engine_reference_semantics_nested_and_copies() {
+  let mut engine = Engine::new();
+  engine.load("let grid = [[0, 0]]; let row = grid[0]; let snapshot = copy(grid); row[1] = 5;").unwrap();
+  assert_eq!(engine.eval("grid"), Ok(Value::array(vec![Value::array(vec![Value::Number(0), Value::Number(5)])])));
+  assert_eq!(engine.eval("snapshot"), Ok(Value::array(vec![Value::array(vec![Value::Number(0), Value::Number(0)])])));
+}
+
+#[test]
+fn engine_reference_semantics_self_containing_array() {
+  let mut engine = Engine::new();
+  engine.load("let a = [1]; a.push(a); let b = copy(a);").unwrap();
+  assert_eq!(engine.eval(r#""" + a"#), Ok(Value::String("[1, [...]]".into())));
+  assert_eq!(engine.eval("[same(b[1], b), same(b, a)]"), Ok(Value::array(vec![Value::Bool(true), Value::Bool(false)])));
+}
+
+#[test]
+fn engine_reference_semantics_compare_self_containing() {
+  let mut engine = Engine::new();
+  engine.load("let a = []; a.push(a); let b = []; b.push(b); let c = [1]; c.push(c); let m = {}; m.m = m; let n = {}; n.m = n;").unwrap();
+  assert_eq!(engine.eval("[a == b, a == c, m == n, b == [b]]"), Ok(Value::array(vec![
+    Value::Bool(true),
+    Value::Bool(false),
+    Value::Bool(true),
+    Value::Bool(true),
+  ])));
+}
+
+#[test]
+fn engine_reference_semantics_share_maps() {
+  let mut engine = Engine::new();
+  engine.load("fn tag(m) { m.seen = true; } let m = { id: 1 }; let alias = m; tag(alias); m.self = m; let c = copy(m);").unwrap();
+  assert_eq!(engine.eval(r#""" + m"#), Ok(Value::String(r#"{"id": 1, "seen": true, "self": {...}}"#.into())));
+  assert_eq!(engine.eval("[same(m, alias), same(c.self, c), same(c, m)]"), Ok(Value::array(vec![
+    Value::Bool(true),
+    Value::Bool(true),
+    Value::Bool(false),
+  ])));
+}
+
+#[test]
+fn engine_deeply_nested_values() {
+  // Copying, comparing, printing and dropping recurse once per level of nesting
+  let mut engine = Engine::new();
+  engine.load("let a = []; let m = {}; let i = 0; while i < 100000 { a = [a]; m = { m: m }; i = i + 1; }").unwrap();
+  assert_eq!(engine.eval("let b = a.copy(); let n = copy(m); [a == b, m == n, a == [b]]"), Ok(Value::array(vec![
+    Value::Bool(true),
+    Value::Bool(true),
+    Value::Bool(false),
+  ])));
+  assert_eq!(engine.eval(r#"len("" + a)"#), Ok(Value::Number(200_002)));
+  drop(engine);
+}
+
+#[test]
+fn engine_closures_share_their_definition() {
+  let mut engine = Engine::new();
+  engine.load("let fs = []; for i in 0..2 { fs.push(|| i); }").unwrap();
+  match (engine.eval("fs[0]"), engine.eval("fs[1]")) {
+    (Ok(Value::Closure(a)), Ok(Value::Closure(b))) => assert!(std::sync::Arc::ptr_eq(&a.def, &b.def)),
+    other => panic!("expected two closures, got {:?}", other),
+  }
+}
+
+#[test]
+fn engine_captures_output() {
+  let mut engine = Engine::new();
+  let stdout = engine.interpreter().capture_stdout();
+  engine.eval(r#"print("from engine");"#).unwrap();
+  assert_eq!(stdout.contents(), "from engine\n");
+}
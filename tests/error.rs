@@ -0,0 +1,107 @@
+use asa::*;
+
+macro_rules! test_source_error {
+  ($func:ident, $test:tt, $code:expr, $message:expr, $span:expr) => (
+    #[test]
+    fn $func() {
+      let err = parse_source($test).unwrap_err();
+      assert_eq!(err.kind.code(), $code);
+      assert_eq!(err.kind.to_string(), $message);
+      assert_eq!(err.span, Some($span));
+    }
+  )
+}
+
+test_source_error!(error_unexpected_character, "let a = 1;\nlet b = $;", "E0001", "unexpected character `$`", Span::new(2, 9, 2, 9));
+test_source_error!(error_unterminated_string, "let a = \"hello", "E0002", "unterminated string literal", Span::new(1, 9, 1, 15));
+test_source_error!(error_syntax, "let a = 1;\n)", "E0003", "syntax error: unexpected RightParen", Span::new(2, 1, 2, 1));
+
+#[test]
+fn error_runtime_codes() {
+  let mut engine = Engine::new();
+  let code = |engine: &mut Engine, source: &str| engine.eval(source).unwrap_err().kind.code();
+  assert_eq!(code(&mut engine, "let x = 5; x.foo()"), "E0114");
+  assert_eq!(code(&mut engine, "len([1], 2)"), "E0115");
+  assert_eq!(code(&mut engine, r#"round(1.25d, 1, "sideways")"#), "E0116");
+  assert_eq!(code(&mut engine, "let a = []; a.pop()"), "E0117");
+  assert_eq!(code(&mut engine, "missing + 1"), "E0103");
+}
+
+#[test]
+fn error_type_mismatch_names_the_type() {
+  let mut engine = Engine::new();
+  let message = |engine: &mut Engine, source: &str| engine.eval(source).unwrap_err().kind.to_string();
+  assert_eq!(message(&mut engine, "-true"), "type mismatch: Cannot apply unary - to bool");
+  assert_eq!(message(&mut engine, "len(1)"), "type mismatch: len() not supported on int");
+  assert_eq!(message(&mut engine, "int([1])"), "type mismatch: int() not supported on array");
+  assert_eq!(message(&mut engine, "float(true)"), "type mismatch: float() not supported on bool");
+  assert_eq!(message(&mut engine, r#""a".foo"#), "type mismatch: string has no property foo");
+  assert_eq!(message(&mut engine, "[1].foo"), "type mismatch: array has no property foo");
+  assert_eq!(message(&mut engine, "let n = 1; n.foo"), "type mismatch: int has no property foo");
+}
+
+#[test]
+fn error_display_includes_code() {
+  let err = AsaError::new(AsaErrorKind::IndexOutOfRange { index: 3, len: 1, place: None });
+  assert_eq!(err.to_string(), "error[E0101]: index 3 out of range for length 1");
+}
+
+#[test]
+fn error_out_of_range_names_the_place() {
+  let err = Engine::new().eval("let g = [[0], [0, 0]]; g[1][5] = 9;").unwrap_err();
+  assert_eq!(err.kind.to_string(), "index 5 out of range for `g[1]` of length 2");
+}
+
+#[test]
+fn error_is_std_error() {
+  let err: Box<dyn std::error::Error> = Box::new(AsaError::new(AsaErrorKind::DivisionByZero));
+  assert_eq!(err.source().unwrap().to_string(), "division by zero");
+}
+
+#[test]
+fn error_json() {
+  let err = AsaError {
+    kind: AsaErrorKind::VariableNotDefined("x".to_string()),
+    span: Some(Span::new(2, 5, 2, 9)),
+    trace: vec![TraceFrame { function: "f".to_string(), call_site: Some(Span::new(4, 1, 4, 3)) }],
+  };
+  assert_eq!(err.to_json("main.asa"), concat!(
+    r#"{"code":"E0103","file":"main.asa","message":"variable `x` is not defined","#,
+    r#""notes":["in f, called from main.asa:4:1"],"#,
+    r#""span":{"end_col":9,"end_line":2,"start_col":5,"start_line":2}}"#
+  ));
+}
+
+#[test]
+fn error_every_code_is_explained() {
+  let kinds = vec![
+    AsaErrorKind::UndefinedFunction,
+    AsaErrorKind::VariableNotDefined(String::new()),
+    AsaErrorKind::DivisionByZero,
+    AsaErrorKind::NumberOverflow,
+    AsaErrorKind::NumberUnderflow,
+    AsaErrorKind::TypeMismatch(String::new()),
+    AsaErrorKind::Generic(String::new()),
+    AsaErrorKind::BreakOutsideLoop,
+    AsaErrorKind::ContinueOutsideLoop,
+    AsaErrorKind::ReturnOutsideFunction,
+    AsaErrorKind::IndexOutOfRange { index: 0, len: 0, place: None },
+    AsaErrorKind::NoMatchingArm(String::new()),
+    AsaErrorKind::KeyNotFound(String::new()),
+    AsaErrorKind::UnknownMethod { type_name: String::new(), method: String::new() },
+    AsaErrorKind::WrongArgumentCount(String::new()),
+    AsaErrorKind::UnknownRoundingMode(String::new()),
+    AsaErrorKind::PopOnEmptyArray,
+    AsaErrorKind::UnexpectedCharacter('$'),
+    AsaErrorKind::UnterminatedString,
+    AsaErrorKind::SyntaxError(String::new()),
+    AsaErrorKind::OutOfFuel(0),
+    AsaErrorKind::CallDepthExceeded(0),
+    AsaErrorKind::MemoryLimitExceeded(0),
+    AsaErrorKind::DeadlineExceeded,
+  ];
+  for kind in kinds {
+    assert!(explain(kind.code()).is_some(), "{} has no explanation", kind.code());
+  }
+  assert_eq!(explain("E9999"), None);
+}
@@ -0,0 +1,125 @@
+use asa::*;
+use rust_decimal::Decimal;
+
+#[derive(Debug)]
+struct Order {
+  id: i32,
+  prices: Vec<Decimal>,
+}
+
+impl HostObject for Order {
+  fn type_name(&self) -> &str {
+    "Order"
+  }
+
+  fn get_property(&self, name: &str) -> Result<Value, AsaErrorKind> {
+    match name {
+      "id" => Ok(self.id.into_value()),
+      "prices" => Ok(self.prices.clone().into_value()),
+      _ => Err(AsaErrorKind::Generic(format!("Order has no property {}", name))),
+    }
+  }
+
+  fn set_property(&mut self, name: &str, value: Value) -> Result<(), AsaErrorKind> {
+    match name {
+      "id" => self.id = i32::from_value(&value)?,
+      "prices" => self.prices = Vec::<Decimal>::from_value(&value)?,
+      _ => return Err(AsaErrorKind::Generic(format!("Cannot set property {} on Order", name))),
+    }
+    Ok(())
+  }
+
+  fn call_method(&mut self, name: &str, args: &[Value]) -> Result<Value, AsaErrorKind> {
+    match name {
+      "total" => Ok(self.prices.iter().sum::<Decimal>().into_value()),
+      "add" => {
+        self.prices.push(Decimal::from_value(&args[0])?);
+        Ok(Value::Number(self.prices.len() as i32))
+      }
+      _ => Err(AsaErrorKind::Generic(format!("Order has no method {}", name))),
+    }
+  }
+}
+
+fn engine_with_order() -> (Engine, HostRef) {
+  let order = HostRef::new(Order { id: 7, prices: vec![Decimal::new(250, 2), Decimal::new(100, 2)] });
+  let mut engine = Engine::new();
+  engine.set_global("order", order.clone().into_value());
+  (engine, order)
+}
+
+#[test]
+fn host_property_access() {
+  let (mut engine, _) = engine_with_order();
+  assert_eq!(engine.eval("order.id"), Ok(Value::Number(7)));
+  assert_eq!(engine.eval("len(order.prices)"), Ok(Value::Number(2)));
+}
+
+#[test]
+fn host_method_call() {
+  let (mut engine, _) = engine_with_order();
+  assert_eq!(engine.eval("order.total()"), Ok(Value::Decimal(Decimal::new(350, 2))));
+}
+
+#[test]
+fn host_mutation_is_visible_to_host() {
+  let (mut engine, order) = engine_with_order();
+  engine.eval("order.id = 8; let count = order.add(1.25d);").unwrap();
+  let order = order.downcast_ref::<Order>().unwrap();
+  assert_eq!(order.id, 8);
+  assert_eq!(order.prices.len(), 3);
+}
+
+#[test]
+fn host_method_on_property_writes_back() {
+  let (mut engine, order) = engine_with_order();
+  engine.eval("order.prices.push(4d);").unwrap();
+  assert_eq!(order.downcast_ref::<Order>().unwrap().prices.len(), 3);
+}
+
+#[test]
+fn host_nested_assignment_writes_back() {
+  let (mut engine, order) = engine_with_order();
+  engine.eval("order.prices[1] = 4d;").unwrap();
+  assert_eq!(order.downcast_ref::<Order>().unwrap().prices[1], Decimal::new(4, 0));
+  let err = engine.eval("order.id.x = 1;").unwrap_err();
+  assert_eq!(err.kind, AsaErrorKind::TypeMismatch("Cannot set property x on int `order.id`".to_string()));
+}
+
+#[test]
+fn host_compound_assignment() {
+  let (mut engine, order) = engine_with_order();
+  engine.eval("order.id += 3; order.id++; order.prices[0] *= 2;").unwrap();
+  let order = order.downcast_ref::<Order>().unwrap();
+  assert_eq!(order.id, 11);
+  assert_eq!(order.prices[0], Decimal::new(500, 2));
+}
+
+#[test]
+fn host_object_passed_to_functions() {
+  let (mut engine, _) = engine_with_order();
+  engine.load("fn describe(o) { return \"order \" + o.id + \": \" + o; }").unwrap();
+  let order = engine.get_global("order").unwrap();
+  assert_eq!(engine.call("describe", &[order]), Ok(Value::String("order 7: <Order>".into())));
+}
+
+#[test]
+fn host_errors() {
+  let (mut engine, _) = engine_with_order();
+  assert_eq!(
+    engine.eval("order.missing").unwrap_err().kind,
+    AsaErrorKind::Generic("Order has no property missing".to_string())
+  );
+  assert_eq!(
+    engine.eval(r#"order.id = "x";"#).unwrap_err().kind,
+    AsaErrorKind::TypeMismatch("Expected an int, got \"x\"".to_string())
+  );
+}
+
+#[test]
+fn host_conversions() {
+  assert_eq!(vec![1, 2].into_value(), Value::array(vec![Value::Number(1), Value::Number(2)]));
+  assert_eq!(Vec::<String>::from_value(&Value::array(vec!["a".into_value()])), Ok(vec!["a".to_string()]));
+  assert_eq!(i64::from_value(&(i64::MAX).into_value()), Ok(i64::MAX));
+  assert!(bool::from_value(&Value::Number(1)).is_err());
+}
@@ -7,6 +7,9 @@ use asa::*;
 
 macro_rules! test_fragment {
   ($func:ident, $test:tt, $expected:expr) => (
+    test_fragment!($func, ArraySemantics::default(), $test, $expected);
+  );
+  ($func:ident, $semantics:expr, $test:tt, $expected:expr) => (
     #[test]
     fn $func() -> Result<(),AsaErrorKind> {
       let tokens = lex($test);
@@ -14,8 +17,9 @@ macro_rules! test_fragment {
         Ok((tokens, tree)) => {
           assert_eq!(tokens.is_done(), true); // Check that input token stream is fully parsed
           let mut interpreter = Interpreter::new();
+          interpreter.set_array_semantics($semantics);
           let result = interpreter.exec(&tree);
-          std::io::stdout().flush();
+          std::io::stdout().flush().unwrap();
           assert_eq!(result, $expected);
           Ok(())
         },
@@ -27,14 +31,15 @@ macro_rules! test_fragment {
 
 // Test interpreter fragments (no main function)
 test_fragment!(interpreter_numeric, r#"123"#, Ok(Value::Number(123)));
-test_fragment!(interpreter_string, r#""hello""#, Ok(Value::String("hello".to_string())));
+test_fragment!(interpreter_string, r#""hello""#, Ok(Value::String("hello".into())));
 test_fragment!(interpreter_bool_true, r#"true"#, Ok(Value::Bool(true)));
 test_fragment!(interpreter_bool_false, r#"false"#, Ok(Value::Bool(false)));
-test_fragment!(interpreter_identifier, r#"x"#, Err(AsaErrorKind::UndefinedFunction));
+test_fragment!(interpreter_undefined_function, r#"missing()"#, Err(AsaErrorKind::UndefinedFunction));
+test_fragment!(interpreter_identifier, r#"x"#, Err(AsaErrorKind::VariableNotDefined("x".into())));
 test_fragment!(interpreter_variable_define, r#"let x = 123;"#, Ok(Value::Number(123)));
 test_fragment!(interpreter_variable_init, r#"let x = 1;"#, Ok(Value::Number(1)));
 test_fragment!(interpreter_variable_bool, r#"let bool = true;"#, Ok(Value::Bool(true)));
-test_fragment!(interpreter_variable_string, r#"let string = "Hello";"#, Ok(Value::String("Hello".to_string())));
+test_fragment!(interpreter_variable_string, r#"let string = "Hello";"#, Ok(Value::String("Hello".into())));
 test_fragment!(interpreter_variable_init_no_space, r#"let x=1;"#, Ok(Value::Number(1)));
 test_fragment!(interpreter_math, r#"1 + 1"#, Ok(Value::Number(2)));
 test_fragment!(interpreter_math_no_space, r#"1-1"#, Ok(Value::Number(0)));
@@ -51,7 +56,7 @@ test_fragment!(interpreter_division_result, r#"0/1"#, Ok(Value::Number(0)));
 test_fragment!(interpreter_reuse_variable, r#"let a = 10; let b = a + 5;"#, Ok(Value::Number(15)));
 test_fragment!(interpreter_return_variable_value, r#"let a = 2; a"#, Ok(Value::Number(2)));
 test_fragment!(interpreter_boolean_comparison, r#"(2 > 1)"#, Ok(Value::Bool(true)));
-test_fragment!(interpreter_variable_string_return, r#"let s = "SomeString"; s"#, Ok(Value::String("SomeString".to_string())));
+test_fragment!(interpreter_variable_string_return, r#"let s = "SomeString"; s"#, Ok(Value::String("SomeString".into())));
 test_fragment!(interpreter_equality_check, r#"let foo = true; let bar = false; let baz = foo == bar; return baz;"#, Ok(Value::Bool(false)));
 
 test_fragment!(interpreter_comparison_check, r#"return !(1 > 0) && true;"#, Ok(Value::Bool(false)));
@@ -163,7 +168,7 @@ fn greet(name = "Guest") {
     return "Hello, " + name;
 }
 return greet();
-"#, Ok(Value::String("Hello, Guest".to_string())));
+"#, Ok(Value::String("Hello, Guest".into())));
 
 // Calling a function with a provided argument, overriding the default
 test_fragment!(function_default_parameter_with_arg, r#"
@@ -171,7 +176,19 @@ fn greet(name = "Guest") {
     return "Hello, " + name;
 }
 return greet("Alice");
-"#, Ok(Value::String("Hello, Alice".to_string())));
+"#, Ok(Value::String("Hello, Alice".into())));
+
+// A function can't see the locals of the function that called it
+test_fragment!(function_no_caller_locals, r#"
+fn inner() {
+    return secret;
+}
+fn outer() {
+    let secret = 1;
+    return inner();
+}
+return outer();
+"#, Err(AsaErrorKind::VariableNotDefined("secret".into())));
 
 test_fragment!(function_array_sum, r#"
     let y = [1, 2, 3];
@@ -207,4 +224,737 @@ fn fibonacci(n) {
 }
 
 return fibonacci(10);
-  "#, Ok(Value::Number(55)));
\ No newline at end of file
+  "#, Ok(Value::Number(55)));
+
+// ### Floats
+test_fragment!(float_literal, r#"2.5"#, Ok(Value::Float(2.5)));
+test_fragment!(float_mixed_addition, r#"1 + 0.5"#, Ok(Value::Float(1.5)));
+test_fragment!(float_uneven_division, r#"7 / 2"#, Ok(Value::Float(3.5)));
+test_fragment!(float_even_division_stays_int, r#"8 / 2"#, Ok(Value::Number(4)));
+test_fragment!(float_negative_exponent, r#"2 ^ -1"#, Ok(Value::Float(0.5)));
+test_fragment!(float_int_equality, r#"1 == 1.0"#, Ok(Value::Bool(true)));
+test_fragment!(float_int_equality_in_collections, r#"[[1] == [1.0], { a: [2] } == { a: [2.0] }, [1] != [1.5]]"#, Ok(Value::array(vec![
+    Value::Bool(true), Value::Bool(true), Value::Bool(true),
+])));
+test_fragment!(float_nan_not_equal_to_itself, r#"let nan = 0.0 / 0.0; nan == nan"#, Ok(Value::Bool(false)));
+test_fragment!(float_nan_in_same_collection, r#"
+let n = 0.0 / 0.0;
+let a = [n];
+let b = a;
+let m = { n: n };
+let k = m;
+[a == b, a == copy(a), m == k]
+"#, Ok(Value::array(vec![Value::Bool(false), Value::Bool(false), Value::Bool(false)])));
+test_fragment!(float_nan_in_same_collection_as_value, ArraySemantics::Value, r#"
+let n = 0.0 / 0.0;
+let a = [n];
+let b = a;
+[a == b, a == copy(a)]
+"#, Ok(Value::array(vec![Value::Bool(false), Value::Bool(false)])));
+test_fragment!(float_nan_comparison, r#"let nan = 0.0 / 0.0; nan < 1 || nan >= 1"#, Ok(Value::Bool(false)));
+test_fragment!(float_average, r#"
+fn average(a, b, c) {
+    return (a + b + c) / 3;
+}
+return average(1, 2, 4);
+"#, Ok(Value::Float(7.0 / 3.0)));
+test_fragment!(float_to_int_conversion, r#"int(-3.9)"#, Ok(Value::Number(-3)));
+test_fragment!(int_to_float_conversion, r#"float(3)"#, Ok(Value::Float(3.0)));
+test_fragment!(float_string_concat, r#""x = " + 2.0"#, Ok(Value::String("x = 2.0".into())));
+
+// ### Big integers
+test_fragment!(bigint_overflow_promotes, r#"2147483647 + 1"#, Ok(Value::BigInt("2147483648".parse().unwrap())));
+test_fragment!(bigint_demotes_when_small, r#"let big = 2147483647 + 1; big - 1"#, Ok(Value::Number(2147483647)));
+test_fragment!(bigint_long_literal, r#"123456789012345678901234567890 * 10"#, Ok(Value::BigInt("1234567890123456789012345678900".parse().unwrap())));
+test_fragment!(bigint_large_exponent, r#"2 ^ 100"#, Ok(Value::BigInt("1267650600228229401496703205376".parse().unwrap())));
+test_fragment!(bigint_huge_exponent, r#"7 ^ 2000000000"#, Err(AsaErrorKind::NumberOverflow));
+test_fragment!(bigint_power_of_small_bases, r#"[0 ^ 0, 0 ^ 5000000000, 1 ^ 5000000000, -1 ^ 5000000001, (-1) ^ 5000000001, 2 ^ 500000 > 0]"#, Ok(Value::array(vec![
+    Value::Number(1), Value::Number(0), Value::Number(1), Value::Number(-1), Value::Number(-1), Value::Bool(true),
+])));
+test_fragment!(bigint_comparison, r#"2 ^ 64 > 2 ^ 63 && 2 ^ 64 == 18446744073709551616"#, Ok(Value::Bool(true)));
+test_fragment!(bigint_negation, r#"-(0 - 2147483647 - 1)"#, Ok(Value::BigInt("2147483648".parse().unwrap())));
+test_fragment!(bigint_min_divided_by_minus_one, r#"let x = -2147483648; return [x / -1, x % -1];"#, Ok(Value::array(vec![
+    Value::BigInt("2147483648".parse().unwrap()), Value::Number(0),
+])));
+test_fragment!(bigint_factorial, r#"
+fn factorial(n) {
+    let result = 1;
+    while n > 0 {
+        result = result * n;
+        n = n - 1;
+    }
+    return result;
+}
+
+return factorial(25);
+"#, Ok(Value::BigInt("15511210043330985984000000".parse().unwrap())));
+
+// ### Decimals
+test_fragment!(decimal_literal, r#"12.50d"#, Ok(Value::Decimal("12.50".parse().unwrap())));
+test_fragment!(decimal_exact_addition, r#"0.1d + 0.2d == 0.3d"#, Ok(Value::Bool(true)));
+test_fragment!(decimal_constructor, r#"decimal("19.99") * 3"#, Ok(Value::Decimal("59.97".parse().unwrap())));
+test_fragment!(decimal_mixed_with_int, r#"5d - 2"#, Ok(Value::Decimal("3".parse().unwrap())));
+test_fragment!(decimal_mixed_with_float, r#"1.5d + 1.5"#, Err(AsaErrorKind::TypeMismatch("Cannot mix decimal and float; convert with decimal() or float() first".to_string())));
+test_fragment!(decimal_comparison, r#"10.00d > 9.99d && 10.00d == 10"#, Ok(Value::Bool(true)));
+test_fragment!(decimal_division_with_scale, r#"div(10d, 3, 2, "half_up")"#, Ok(Value::Decimal("3.33".parse().unwrap())));
+test_fragment!(decimal_round_half_even, r#"round(2.345d, 2)"#, Ok(Value::Decimal("2.34".parse().unwrap())));
+test_fragment!(decimal_round_half_up, r#"round(2.345d, 2, "half_up")"#, Ok(Value::Decimal("2.35".parse().unwrap())));
+test_fragment!(decimal_power, r#"[1.10d ^ 2, 1d ^ 2000000000, 2d ^ 10]"#, Ok(Value::array(vec![
+    Value::Decimal("1.2100".parse().unwrap()), Value::Decimal("1".parse().unwrap()), Value::Decimal("1024".parse().unwrap()),
+])));
+test_fragment!(decimal_power_overflow, r#"2d ^ 1000"#, Err(AsaErrorKind::NumberOverflow));
+test_fragment!(decimal_formatting, r#""Total: " + round(7d, 2)"#, Ok(Value::String("Total: 7.00".into())));
+
+// ### Short-circuit evaluation
+test_fragment!(short_circuit_and_skips_out_of_range_index, r#"
+let arr = [0, 0, 1];
+let i = 3;
+i < arr.length && arr[i] == 0
+"#, Ok(Value::Bool(false)));
+test_fragment!(short_circuit_and_skips_call, r#"
+fn explode(x) {
+    return 10 / x > 1;
+}
+let x = 0;
+x != 0 && explode(x)
+"#, Ok(Value::Bool(false)));
+test_fragment!(short_circuit_or_skips_call, r#"true || undefined()"#, Ok(Value::Bool(true)));
+test_fragment!(short_circuit_evaluates_right_when_needed, r#"false || undefined()"#, Err(AsaErrorKind::UndefinedFunction));
+test_fragment!(short_circuit_loop_guard, r#"
+let tape = [1, 1, 0, 1];
+let pos = 0;
+while pos < tape.length && tape[pos] == 1 {
+    pos = pos + 1;
+}
+return pos;
+"#, Ok(Value::Number(2)));
+
+// ### Control flow
+test_fragment!(break_outside_loop, r#"let x = 1; break;"#, Err(AsaErrorKind::BreakOutsideLoop));
+test_fragment!(continue_outside_loop, r#"continue;"#, Err(AsaErrorKind::ContinueOutsideLoop));
+test_fragment!(break_escaping_function, r#"
+fn leave() {
+    break;
+}
+while true {
+    leave();
+}
+"#, Err(AsaErrorKind::BreakOutsideLoop));
+test_fragment!(while_with_continue, r#"
+let i = 0;
+let odd = 0;
+while i < 10 {
+    i = i + 1;
+    if i % 2 == 0 {
+        continue;
+    }
+    odd = odd + 1;
+}
+return odd;
+"#, Ok(Value::Number(5)));
+test_fragment!(return_from_inside_loop, r#"
+fn firstOver(limit) {
+    let i = 0;
+    while true {
+        if i * i > limit {
+            return i;
+        }
+        i = i + 1;
+    }
+}
+return firstOver(50);
+"#, Ok(Value::Number(8)));
+
+// ### Closures
+test_fragment!(closure_counter, r#"
+fn makeCounter() {
+    let count = 0;
+    return fn () {
+        count = count + 1;
+        return count;
+    };
+}
+let a = makeCounter();
+let b = makeCounter();
+let x = a();
+x = a();
+let y = b();
+return [x, y];
+"#, Ok(Value::array(vec![Value::Number(2), Value::Number(1)])));
+test_fragment!(closure_short_form, r#"
+fn apply(f, x) {
+    return f(x);
+}
+return apply(|x| x * 2, 21);
+"#, Ok(Value::Number(42)));
+test_fragment!(closure_captures_argument, r#"
+fn adder(n) {
+    return |x| x + n;
+}
+let addFive = adder(5);
+return addFive(10);
+"#, Ok(Value::Number(15)));
+test_fragment!(closure_captures_by_reference, r#"
+fn later() {
+    let n = 1;
+    let get = || n;
+    n = 5;
+    return get();
+}
+return later();
+"#, Ok(Value::Number(5)));
+test_fragment!(closure_updates_global, r#"
+let total = 0;
+let add = fn (x) { total = total + x; };
+let r = add(2);
+r = add(3);
+total
+"#, Ok(Value::Number(5)));
+test_fragment!(closure_display, r#""" + |a, b| a"#, Ok(Value::String("<fn anonymous/2>".into())));
+
+// ### Calling expressions
+test_fragment!(call_returned_function, r#"
+fn add(a) {
+    return |b| |c| a + b + c;
+}
+return add(1)(2)(3);
+"#, Ok(Value::Number(6)));
+test_fragment!(call_from_array, r#"
+fn double(x) {
+    return x * 2;
+}
+let handlers = [double, |x| x + 1];
+let i = 1;
+return [handlers[0](5), handlers[i](5)];
+"#, Ok(Value::array(vec![Value::Number(10), Value::Number(6)])));
+test_fragment!(call_parenthesized_function, r#"(fn (x) { return x * x; })(7)"#, Ok(Value::Number(49)));
+test_fragment!(call_function_property, r#"
+let m = { f: |x| x * 2, keys: |x| x + 1 };
+[m.f(3), (m.f)(3), m["f"](3), len(m.keys())]
+"#, Ok(Value::array(vec![Value::Number(6), Value::Number(6), Value::Number(6), Value::Number(2)])));
+test_fragment!(call_missing_function_property, r#"let m = { f: 1 }; m.f(3)"#, Err(AsaErrorKind::UnknownMethod { type_name: "map".to_string(), method: "f".to_string() }));
+test_fragment!(call_non_function, r#"
+let handlers = [1];
+handlers[0](2)
+"#, Err(AsaErrorKind::TypeMismatch("1 is not a function".to_string())));
+
+// ### Methods
+test_fragment!(method_writes_back_through_index, r#"
+let rows = [[1], [2]];
+let i = 1;
+rows[i].push(3);
+rows[0].prepend(0);
+rows
+"#, Ok(Value::array(vec![
+    Value::array(vec![Value::Number(0), Value::Number(1)]),
+    Value::array(vec![Value::Number(2), Value::Number(3)]),
+])));
+test_fragment!(method_index_evaluated_once, r#"
+let rows = [[], []];
+let calls = 0;
+fn next() {
+    calls = calls + 1;
+    return calls;
+}
+rows[next()].push(1);
+[rows, calls]
+"#, Ok(Value::array(vec![
+    Value::array(vec![Value::array(vec![]), Value::array(vec![Value::Number(1)])]),
+    Value::Number(1),
+])));
+test_fragment!(method_on_literal, r#"[1, 2].push(3)"#, Ok(Value::array(vec![Value::Number(1), Value::Number(2), Value::Number(3)])));
+test_fragment!(method_on_call_result, r#"
+fn getTape() {
+    return [1, 2, 3];
+}
+getTape().pop()
+"#, Ok(Value::Number(3)));
+test_fragment!(method_on_strings, r#"" a,b ".trim().upper().split(",")"#, Ok(Value::array(vec![
+    Value::String("A".into()),
+    Value::String("B".into()),
+])));
+test_fragment!(method_join_and_contains, r#"[[1, 2].join("+"), ["a"].contains("a"), "abc".contains("d")]"#, Ok(Value::array(vec![
+    Value::String("1+2".into()),
+    Value::Bool(true),
+    Value::Bool(false),
+])));
+test_fragment!(method_unknown, r#"let x = 5; x.foo()"#, Err(AsaErrorKind::UnknownMethod { type_name: "int".to_string(), method: "foo".to_string() }));
+
+// ### Nested assignment
+test_fragment!(assign_grid_cell, r#"
+let grid = [[0, 0], [0, 0]];
+let i = 1;
+grid[i][0] = 5;
+grid[0][1] = grid[i][0] + 1;
+grid
+"#, Ok(Value::array(vec![
+    Value::array(vec![Value::Number(0), Value::Number(6)]),
+    Value::array(vec![Value::Number(5), Value::Number(0)]),
+])));
+test_fragment!(assign_three_levels, r#"
+let cube = [[[1]]];
+cube[0][0][0] = 2;
+cube[0][0]
+"#, Ok(Value::array(vec![Value::Number(2)])));
+test_fragment!(assign_nested_in_function, r#"
+fn mark(tape, row, col) {
+    tape[row][col] = 1;
+    return tape;
+}
+mark([[0], [0, 0]], 1, 1)
+"#, Ok(Value::array(vec![
+    Value::array(vec![Value::Number(0)]),
+    Value::array(vec![Value::Number(0), Value::Number(1)]),
+])));
+test_fragment!(assign_nested_out_of_range, r#"
+let grid = [[0, 0], [0]];
+grid[1][1] = 5;
+"#, Err(AsaErrorKind::IndexOutOfRange { index: 1, len: 1, place: Some("grid[1]".to_string()) }));
+test_fragment!(assign_nested_outer_out_of_range, r#"
+let grid = [[0, 0], [0]];
+grid[2][0] = 5;
+"#, Err(AsaErrorKind::IndexOutOfRange { index: 2, len: 2, place: Some("grid".to_string()) }));
+test_fragment!(assign_nested_into_non_array, r#"
+let grid = [[0, 1], 2];
+grid[1][0] = 5;
+"#, Err(AsaErrorKind::TypeMismatch("Cannot index into int `grid[1]`".to_string())));
+test_fragment!(assign_property_of_non_object, r#"
+let rows = [1];
+rows[0].name = "a";
+"#, Err(AsaErrorKind::TypeMismatch("Cannot set property name on int `rows[0]`".to_string())));
+
+// ### Array semantics
+test_fragment!(array_reference_semantics_by_default, r#"
+fn grow(tape) {
+    tape.push(0);
+    tape[0] = 9;
+}
+let tape = [1];
+let alias = tape;
+alias.push(2);
+grow(tape);
+[tape, same(tape, alias)]
+"#, Ok(Value::array(vec![
+    Value::array(vec![Value::Number(9), Value::Number(2), Value::Number(0)]),
+    Value::Bool(true),
+])));
+test_fragment!(array_value_semantics, ArraySemantics::Value, r#"
+fn grow(tape) {
+    tape.push(0);
+    tape[0] = 9;
+}
+let tape = [1];
+let alias = tape;
+alias.push(2);
+grow(tape);
+[tape, alias]
+"#, Ok(Value::array(vec![
+    Value::array(vec![Value::Number(1)]),
+    Value::array(vec![Value::Number(1), Value::Number(2)]),
+])));
+test_fragment!(array_copy_builtin_and_method, r#"
+let grid = [[1], [2]];
+let a = copy(grid);
+let b = grid.copy();
+a[0][0] = 5;
+[grid, a == grid, b == grid]
+"#, Ok(Value::array(vec![
+    Value::array(vec![Value::array(vec![Value::Number(1)]), Value::array(vec![Value::Number(2)])]),
+    Value::Bool(false),
+    Value::Bool(true),
+])));
+test_fragment!(array_same_is_equality_for_values, ArraySemantics::Value, r#"[same([1, 2], [1, 2]), same([1], [2]), same(1, 1.0)]"#, Ok(Value::array(vec![
+    Value::Bool(true),
+    Value::Bool(false),
+    Value::Bool(true),
+])));
+
+test_fragment!(array_shared_until_changed, ArraySemantics::Value, r#"
+let a = [1];
+let b = a.push(2);
+a.push(3);
+let grid = [[0], [0]];
+let row = grid[0];
+grid[0][0] = 5;
+row.push(1);
+[a, b, grid, row]
+"#, Ok(Value::array(vec![
+    Value::array(vec![Value::Number(1), Value::Number(2), Value::Number(3)]),
+    Value::array(vec![Value::Number(1), Value::Number(2)]),
+    Value::array(vec![Value::array(vec![Value::Number(5)]), Value::array(vec![Value::Number(0)])]),
+    Value::array(vec![Value::Number(0), Value::Number(1)]),
+])));
+test_fragment!(array_returned_temporary_is_a_copy, ArraySemantics::Value, r#"
+let tape = [1, 2];
+fn getTape() {
+    return tape;
+}
+getTape().pop();
+tape
+"#, Ok(Value::array(vec![Value::Number(1), Value::Number(2)])));
+test_fragment!(array_contains_itself_as_value, ArraySemantics::Value, r#"
+let a = [1];
+a.push(a);
+a[0] = a;
+a
+"#, Ok(Value::array(vec![
+    Value::array(vec![Value::Number(1), Value::array(vec![Value::Number(1)])]),
+    Value::array(vec![Value::Number(1)]),
+])));
+
+// ### For loops
+test_fragment!(for_array_sum, r#"
+let total = 0;
+for x in [1, 2, 3, 4] {
+    total = total + x;
+}
+total
+"#, Ok(Value::Number(10)));
+test_fragment!(for_index_and_value, r#"
+let out = [];
+for i, c in "ab" {
+    out.push(i + c);
+}
+out
+"#, Ok(Value::array(vec![Value::String("0a".into()), Value::String("1b".into())])));
+test_fragment!(for_ranges, r#"
+let out = [];
+for n in 0..3 { out.push(n); }
+for n in 0..=3 step 3 { out.push(n); }
+for n in 3..0 step -2 { out.push(n); }
+for n in 2..2 { out.push(n); }
+out
+"#, Ok(Value::array(vec![
+    Value::Number(0), Value::Number(1), Value::Number(2),
+    Value::Number(0), Value::Number(3),
+    Value::Number(3), Value::Number(1),
+])));
+test_fragment!(for_break_and_continue, r#"
+let out = [];
+for n in 0..10 {
+    if (n == 1) { continue; }
+    if (n == 4) { break; }
+    out.push(n);
+}
+out
+"#, Ok(Value::array(vec![Value::Number(0), Value::Number(2), Value::Number(3)])));
+test_fragment!(for_return_from_function, r#"
+fn find(xs, wanted) {
+    for i, x in xs {
+        if (x == wanted) { return i; }
+    }
+    return -1;
+}
+[find([5, 6, 7], 7), find([5], 1)]
+"#, Ok(Value::array(vec![Value::Number(2), Value::Number(-1)])));
+test_fragment!(for_variable_scoped_to_body, r#"
+let x = "outer";
+for x in [1, 2] { }
+for y in [1, 2] { }
+x
+"#, Ok(Value::String("outer".into())));
+test_fragment!(for_variable_gone_after_loop, r#"
+for y in [1, 2] { }
+y
+"#, Err(AsaErrorKind::VariableNotDefined("y".into())));
+test_fragment!(for_closures_capture_each_iteration, r#"
+let fs = [];
+for i in 0..3 {
+    fs.push(fn () { return i; });
+}
+[fs[0](), fs[2]()]
+"#, Ok(Value::array(vec![Value::Number(0), Value::Number(2)])));
+test_fragment!(for_closures_capture_each_iteration_in_function, r#"
+fn counters() {
+    let fs = [];
+    for i, x in [10, 20] {
+        fs.push(|| i + x);
+    }
+    return fs;
+}
+let fs = counters();
+[fs[0](), fs[1]()]
+"#, Ok(Value::array(vec![Value::Number(10), Value::Number(21)])));
+test_fragment!(for_changing_array_iterates_original, r#"
+let xs = [1, 2];
+for x in xs {
+    xs.push(x);
+}
+xs
+"#, Ok(Value::array(vec![Value::Number(1), Value::Number(2), Value::Number(1), Value::Number(2)])));
+test_fragment!(for_over_number, r#"for x in 5 { }"#, Err(AsaErrorKind::TypeMismatch("Cannot iterate over int".into())));
+test_fragment!(for_zero_step, r#"for x in 0..5 step 0 { }"#, Err(AsaErrorKind::Generic("Range step must not be zero".into())));
+
+// ### Compound assignment
+test_fragment!(compound_assign_operators, r#"
+let x = 2;
+x ^= 3;
+x %= 5;
+x *= 4;
+x /= 3;
+x -= 1;
+x += 0.5;
+let s = "a";
+s += 1;
+[x, s]
+"#, Ok(Value::array(vec![Value::Float(3.5), Value::String("a1".into())])));
+test_fragment!(compound_assign_index_evaluated_once, r#"
+let tape = [[1], [2]];
+let calls = 0;
+fn next() {
+    calls += 1;
+    return calls;
+}
+tape[next()][0] += 10;
+[tape, calls]
+"#, Ok(Value::array(vec![
+    Value::array(vec![Value::array(vec![Value::Number(1)]), Value::array(vec![Value::Number(12)])]),
+    Value::Number(1),
+])));
+test_fragment!(increment_and_decrement, r#"
+let pos = 0;
+let tape = [5, 5];
+pos++;
+tape[pos]--;
+tape[0]++;
+[pos, tape]
+"#, Ok(Value::array(vec![Value::Number(1), Value::array(vec![Value::Number(6), Value::Number(4)])])));
+test_fragment!(compound_assign_undefined, r#"missing += 1;"#, Err(AsaErrorKind::VariableNotDefined("missing".into())));
+test_fragment!(compound_assign_type_error, r#"let flag = true; flag -= 1;"#, Err(AsaErrorKind::TypeMismatch("Type error in binary expression: expected matching types".into())));
+
+// ### Match
+test_fragment!(match_literals_and_alternatives, r#"
+fn name(n) {
+    return match n {
+        0 => "zero",
+        1 | 2 => "few",
+        -1 => "minus one",
+        "x" => "ex",
+        true => "yes",
+        _ => "many",
+    };
+}
+[name(0), name(2), name(-1), name("x"), name(true), name(9)]
+"#, Ok(Value::array(vec![
+    Value::String("zero".into()), Value::String("few".into()), Value::String("minus one".into()),
+    Value::String("ex".into()), Value::String("yes".into()), Value::String("many".into()),
+])));
+test_fragment!(match_ranges, r#"
+let out = [];
+for n in [2, 3, 5, 5.5, "5"] {
+    out.push(match n { 0..3 => "low", 3..=5 => "mid", _ => "other" });
+}
+out
+"#, Ok(Value::array(vec![
+    Value::String("low".into()), Value::String("mid".into()), Value::String("mid".into()),
+    Value::String("other".into()), Value::String("other".into()),
+])));
+test_fragment!(match_array_destructuring, r#"
+let out = [];
+for v in [[1, 2], [3, [4, 5]], [6], []] {
+    out.push(match v {
+        [] => 0,
+        [a, [b, c]] => a + b + c,
+        [a, b] => a * b,
+        [_] => -1,
+    });
+}
+out
+"#, Ok(Value::array(vec![Value::Number(2), Value::Number(12), Value::Number(-1), Value::Number(0)])));
+test_fragment!(match_binding_and_guard, r#"
+fn size(n) {
+    return match n {
+        n if n > 100 => "big " + n,
+        small if small < 0 => "negative",
+        other => "just " + other,
+    };
+}
+[size(500), size(-3), size(7)]
+"#, Ok(Value::array(vec![
+    Value::String("big 500".into()), Value::String("negative".into()), Value::String("just 7".into()),
+])));
+test_fragment!(match_bindings_scoped_to_arm, r#"
+let a = "kept";
+let b = 0;
+match [1, 2] {
+    [a, b] if a > 5 => 0,
+    [a, c] => { b = a + c; }
+}
+[a, b]
+"#, Ok(Value::array(vec![Value::String("kept".into()), Value::Number(3)])));
+test_fragment!(match_closure_captures_binding, r#"
+let f = match 3 { n => |x| x + n };
+f(1)
+"#, Ok(Value::Number(4)));
+test_fragment!(match_state_table, r#"
+let state = 0;
+let steps = 0;
+while (true) {
+    steps++;
+    match state {
+        0 => { state = 1; }
+        1 => {
+            state = 2;
+            continue;
+        }
+        _ => { break; }
+    }
+}
+steps
+"#, Ok(Value::Number(3)));
+test_fragment!(match_no_arm, r#"match [1] { [] => 0, [a, b] => 1 }"#, Err(AsaErrorKind::NoMatchingArm("[1]".into())));
+test_fragment!(match_guard_not_bool, r#"match 1 { n if n => 0 }"#, Err(AsaErrorKind::TypeMismatch("Match guard must be boolean".into())));
+
+// ### Maps
+test_fragment!(map_literal_and_access, r#"
+let m = { "a": 1, b: 2, 3: "three", true: [4], };
+[m["a"], m.b, m[3], m[true][0], len(m), len({})]
+"#, Ok(Value::array(vec![
+    Value::Number(1), Value::Number(2), Value::String("three".into()), Value::Number(4),
+    Value::Number(4), Value::Number(0),
+])));
+test_fragment!(map_keys_keep_insertion_order, r#"
+let m = { z: 1, a: 2 };
+m.m = 3;
+m.z = 4;
+"" + m
+"#, Ok(Value::String(r#"{"z": 4, "a": 2, "m": 3}"#.into())));
+test_fragment!(map_assignment, r#"
+let m = { rows: [[1], [2]], counts: {} };
+m["total"] = 0;
+m.rows[1].push(3);
+m.counts.x = 1;
+m.counts["x"] += 4;
+m.total++;
+[m.total, m.rows, m.counts.x]
+"#, Ok(Value::array(vec![
+    Value::Number(1),
+    Value::array(vec![Value::array(vec![Value::Number(1)]), Value::array(vec![Value::Number(2), Value::Number(3)])]),
+    Value::Number(5),
+])));
+test_fragment!(map_methods, r#"
+let m = { a: 1, b: 2, c: 3 };
+let removed = m.remove("b");
+[m.keys(), m.values(), m.has("a"), m.has("b"), removed]
+"#, Ok(Value::array(vec![
+    Value::array(vec![Value::String("a".into()), Value::String("c".into())]),
+    Value::array(vec![Value::Number(1), Value::Number(3)]),
+    Value::Bool(true), Value::Bool(false), Value::Number(2),
+])));
+test_fragment!(map_for_loops, r#"
+let m = { x: 1, y: 2 };
+let keys = "";
+let total = 0;
+for k in m { keys = keys + k; }
+for k, v in m { total += v; }
+[keys, total]
+"#, Ok(Value::array(vec![Value::String("xy".into()), Value::Number(3)])));
+test_fragment!(map_assignment_copies, ArraySemantics::Value, r#"
+let a = { n: 1, inner: { n: 1 } };
+let b = a;
+b.n = 2;
+b.inner.n = 2;
+let c = a.copy();
+[a == { n: 1, inner: { n: 1 } }, b.inner.n, c == a]
+"#, Ok(Value::array(vec![Value::Bool(true), Value::Number(2), Value::Bool(true)])));
+test_fragment!(map_equality_ignores_order, r#"{ a: 1, b: 2 } == { b: 2, a: 1 }"#, Ok(Value::Bool(true)));
+test_fragment!(map_missing_key, r#"let m = { a: 1 }; m.b"#, Err(AsaErrorKind::KeyNotFound("\"b\"".into())));
+test_fragment!(map_remove_missing_key, r#"let m = {}; m.remove(1)"#, Err(AsaErrorKind::KeyNotFound("1".into())));
+test_fragment!(map_invalid_key, r#"let m = {}; m[[1]] = 2;"#, Err(AsaErrorKind::TypeMismatch("Map keys must be strings, integers or bools, got array".into())));
+
+// ### Displaying values
+test_fragment!(display_concat_array, r#""tape: " + [1, 2]"#, Ok(Value::String("tape: [1, 2]".into())));
+test_fragment!(display_concat_nested_strings, r#"[1, "b", [true]] + "!""#, Ok(Value::String("[1, \"b\", [true]]!".into())));
+test_fragment!(display_concat_float, r#""x = " + 2.0"#, Ok(Value::String("x = 2.0".into())));
+test_fragment!(display_concat_function, r#"fn add(a, b) { return a + b; } "f: " + add"#, Ok(Value::String("f: <fn add/2>".into())));
+
+#[test]
+fn display_and_repr() {
+  let value = Value::array(vec![Value::String("say \"hi\"".into()), Value::Decimal("1.50".parse().unwrap())]);
+  assert_eq!(value.to_string(), r#"["say \"hi\"", 1.50d]"#);
+  assert_eq!(Value::String("hi".into()).to_string(), "hi");
+  assert_eq!(Value::String("hi".into()).repr(), "\"hi\"");
+  assert_eq!(Value::Decimal("2".parse().unwrap()).to_string(), "2");
+  assert_eq!(Value::Decimal("2".parse().unwrap()).repr(), "2d");
+}
+test_fragment!(display_decimal_in_array, r#""" + [2d, 2, 1.5d, 1.5]"#, Ok(Value::String("[2d, 2, 1.5d, 1.5]".into())));
+
+// ### Output
+macro_rules! test_output {
+  ($func:ident, $test:tt, $stdout:expr, $stderr:expr) => (
+    #[test]
+    fn $func() {
+      let (_, tree) = program(lex($test)).unwrap();
+      let mut interpreter = Interpreter::new();
+      let stdout = interpreter.capture_stdout();
+      let stderr = interpreter.capture_stderr();
+      interpreter.exec(&tree).unwrap();
+      assert_eq!(stdout.contents(), $stdout);
+      assert_eq!(stderr.contents(), $stderr);
+    }
+  )
+}
+
+test_output!(output_print, r#"print("hi"); print([1, "a"]);"#, "hi\n[1, \"a\"]\n", "");
+test_output!(output_eprint, r#"print("out"); eprint("err");"#, "out\n", "err\n");
+test_output!(output_print_in_loop, r#"
+let i = 0;
+while i < 3 {
+    print(i);
+    i = i + 1;
+}
+"#, "0\n1\n2\n", "");
+
+#[test]
+fn output_take_empties_buffer() {
+  let (_, tree) = program(lex(r#"print(1);"#)).unwrap();
+  let mut interpreter = Interpreter::new();
+  let stdout = interpreter.capture_stdout();
+  interpreter.exec(&tree).unwrap();
+  assert_eq!(stdout.take(), "1\n");
+  assert_eq!(stdout.contents(), "");
+}
+
+// ### Error locations
+macro_rules! test_error {
+  ($func:ident, $test:tt, $check:expr) => (
+    #[test]
+    fn $func() {
+      let (_, tree) = program(lex($test)).unwrap();
+      let mut interpreter = Interpreter::new();
+      let err = interpreter.eval(&tree).unwrap_err();
+      let check: fn(AsaError) = $check;
+      check(err);
+    }
+  )
+}
+
+test_error!(error_span_top_level, r#"let a = [1, 2];
+let b = a[5];"#, |err| {
+  assert_eq!(err.kind, AsaErrorKind::IndexOutOfRange { index: 5, len: 2, place: None });
+  assert_eq!(err.span, Some(Span::new(2, 1, 2, 13)));
+  assert!(err.trace.is_empty());
+});
+test_error!(error_trace_nested_calls, r#"fn inner(a) {
+    return a[3];
+}
+fn outer() {
+    let a = [1];
+    return inner(a);
+}
+outer();"#, |err| {
+  assert_eq!(err.span, Some(Span::new(2, 5, 2, 16)));
+  let frames: Vec<(String, Option<u32>)> = err.trace.iter()
+    .map(|frame| (frame.function.clone(), frame.call_site.map(|site| site.start_line)))
+    .collect();
+  assert_eq!(frames, vec![("inner".to_string(), Some(6)), ("outer".to_string(), Some(8))]);
+});
+test_error!(error_traceback_render, r#"fn fail() {
+    return 1 + true;
+}
+fail();"#, |err| {
+  assert_eq!(err.render_traceback("test.asa"), "Traceback (most recent call last):
+  in fail, called from test.asa:4:1
+error[E0104]: type mismatch: Invalid types for `+` operation
+  --> test.asa:2:5");
+});
@@ -106,4 +106,97 @@ fn lexer_test_09() {
     TokenKind::RightCurly,
     TokenKind::EOF,
   ]);
-}
\ No newline at end of file
+}
+#[test]
+fn lexer_test_10() {
+  assert_eq!(test_lex("|x| a || b"),vec![
+    TokenKind::Pipe,
+    TokenKind::Alpha,
+    TokenKind::Pipe,
+    TokenKind::Alpha,
+    TokenKind::LogicalOr,
+    TokenKind::Alpha,
+    TokenKind::EOF,
+  ]);
+}
+
+#[test]
+fn lexer_test_11() {
+  assert_eq!(test_lex("for i in 0..n step 2 format"),vec![
+    TokenKind::For,
+    TokenKind::Alpha,
+    TokenKind::In,
+    TokenKind::Digit,
+    TokenKind::Dot,
+    TokenKind::Dot,
+    TokenKind::Alpha,
+    TokenKind::Step,
+    TokenKind::Digit,
+    TokenKind::Alpha,
+    TokenKind::Alpha,
+    TokenKind::Alpha,
+    TokenKind::Alpha,
+    TokenKind::Alpha,
+    TokenKind::Alpha,
+    TokenKind::EOF,
+  ]);
+}
+
+#[test]
+fn lexer_test_12() {
+  assert_eq!(test_lex("a += b -= c *= d /= e %= f ^= g++ h-- - -i"),vec![
+    TokenKind::Alpha,
+    TokenKind::PlusEqual,
+    TokenKind::Alpha,
+    TokenKind::DashEqual,
+    TokenKind::Alpha,
+    TokenKind::MultiplyEqual,
+    TokenKind::Alpha,
+    TokenKind::SlashEqual,
+    TokenKind::Alpha,
+    TokenKind::ModulusEqual,
+    TokenKind::Alpha,
+    TokenKind::ExponentEqual,
+    TokenKind::Alpha,
+    TokenKind::PlusPlus,
+    TokenKind::Alpha,
+    TokenKind::DashDash,
+    TokenKind::Dash,
+    TokenKind::Dash,
+    TokenKind::Alpha,
+    TokenKind::EOF,
+  ]);
+}
+
+#[test]
+fn lexer_test_13() {
+  assert_eq!(test_lex("match x { _ => 1 } matches"),vec![
+    TokenKind::Match,
+    TokenKind::Alpha,
+    TokenKind::LeftCurly,
+    TokenKind::Underscore,
+    TokenKind::FatArrow,
+    TokenKind::Digit,
+    TokenKind::RightCurly,
+    TokenKind::Alpha,
+    TokenKind::Alpha,
+    TokenKind::Alpha,
+    TokenKind::Alpha,
+    TokenKind::Alpha,
+    TokenKind::Alpha,
+    TokenKind::Alpha,
+    TokenKind::EOF,
+  ]);
+}
+
+#[test]
+fn lexer_test_14() {
+  assert_eq!(test_lex("{ a: 1 }"),vec![
+    TokenKind::LeftCurly,
+    TokenKind::Alpha,
+    TokenKind::Colon,
+    TokenKind::Digit,
+    TokenKind::RightCurly,
+    TokenKind::EOF,
+  ]);
+}
@@ -0,0 +1,101 @@
+use std::time::Duration;
+
+use asa::*;
+
+fn engine_with(limits: Limits) -> Engine {
+  let mut engine = Engine::new();
+  engine.set_limits(limits);
+  engine
+}
+
+#[test]
+fn limits_fuel_stops_infinite_loop() {
+  let mut engine = engine_with(Limits { fuel: Some(10_000), ..Limits::default() });
+  let err = engine.eval("let i = 0; while true { i = i + 1; }").unwrap_err();
+  assert_eq!(err.kind, AsaErrorKind::OutOfFuel(10_000));
+}
+
+#[test]
+fn limits_fuel_allows_bounded_work() {
+  let mut engine = engine_with(Limits { fuel: Some(10_000), ..Limits::default() });
+  assert_eq!(engine.eval("let i = 0; while i < 10 { i = i + 1; } i"), Ok(Value::Number(10)));
+  assert!(engine.interpreter().usage().steps > 0);
+}
+
+#[test]
+fn limits_call_depth() {
+  let mut engine = engine_with(Limits { max_call_depth: Some(8), ..Limits::default() });
+  engine.load("fn down(n) { if n == 0 { return 0; } return down(n - 1); }").unwrap();
+  assert_eq!(engine.call("down", &[Value::Number(7)]), Ok(Value::Number(0)));
+  let err = engine.call("down", &[Value::Number(20)]).unwrap_err();
+  assert_eq!(err.kind, AsaErrorKind::CallDepthExceeded(8));
+  assert_eq!(err.trace.len(), 8);
+}
+
+#[test]
+fn limits_memory() {
+  let mut engine = engine_with(Limits { max_memory: Some(1_000), ..Limits::default() });
+  let err = engine.eval(r#"let s = ""; while true { s = s + "0123456789"; }"#).unwrap_err();
+  assert_eq!(err.kind, AsaErrorKind::MemoryLimitExceeded(1_000));
+}
+
+#[test]
+fn limits_memory_counts_big_integers() {
+  let mut engine = engine_with(Limits { max_memory: Some(1_000), ..Limits::default() });
+  let err = engine.eval("let n = 3; while true { n = n * n; }").unwrap_err();
+  assert_eq!(err.kind, AsaErrorKind::MemoryLimitExceeded(1_000));
+}
+
+#[test]
+fn limits_memory_counts_arrays() {
+  let mut engine = engine_with(Limits { max_memory: Some(10_000), ..Limits::default() });
+  let err = engine.eval("let a = []; while true { a.push(1); }").unwrap_err();
+  assert_eq!(err.kind, AsaErrorKind::MemoryLimitExceeded(10_000));
+}
+
+#[test]
+fn limits_memory_counts_each_push_once() {
+  // Growing an array one element at a time costs one element per push, not the whole array
+  let budget = 1_000 * std::mem::size_of::<Value>();
+  let mut engine = engine_with(Limits { max_memory: Some(budget), ..Limits::default() });
+  assert_eq!(engine.eval("let a = []; let i = 0; while i < 900 { a.push(i); i = i + 1; } a.length"), Ok(Value::Number(900)));
+  assert_eq!(engine.interpreter().usage().memory, 900 * std::mem::size_of::<Value>());
+}
+
+#[test]
+fn limits_memory_counts_copies() {
+  let budget = 1_000 * std::mem::size_of::<Value>();
+  let mut engine = engine_with(Limits { max_memory: Some(budget), ..Limits::default() });
+  engine.load("let a = []; let i = 0; while i < 100 { a.push(i); i = i + 1; }").unwrap();
+  let err = engine.eval("while true { copy(a); }").unwrap_err();
+  assert_eq!(err.kind, AsaErrorKind::MemoryLimitExceeded(budget));
+  let err = engine.eval("while true { a.copy(); }").unwrap_err();
+  assert_eq!(err.kind, AsaErrorKind::MemoryLimitExceeded(budget));
+}
+
+#[test]
+fn limits_deadline() {
+  let mut engine = engine_with(Limits { deadline: Limits::deadline_in(Duration::from_millis(50)), ..Limits::default() });
+  let err = engine.eval("while true { }").unwrap_err();
+  assert_eq!(err.kind, AsaErrorKind::DeadlineExceeded);
+}
+
+#[test]
+fn limits_reset_with_new_limits() {
+  let mut engine = engine_with(Limits { fuel: Some(50), ..Limits::default() });
+  assert!(engine.eval("while true { }").is_err());
+  engine.set_limits(Limits { fuel: Some(50), ..Limits::default() });
+  assert_eq!(engine.eval("1 + 1"), Ok(Value::Number(2)));
+}
+
+#[test]
+fn limits_deep_recursion() {
+  // Deep recursion grows the native stack instead of overflowing it, only the limit stops it
+  let mut engine = Engine::new();
+  engine.load("fn down(n) { if n == 0 { return 0; } return 1 + down(n - 1); }").unwrap();
+  assert_eq!(engine.call("down", &[Value::Number(100_000)]), Ok(Value::Number(100_000)));
+
+  engine.set_limits(Limits { max_call_depth: Some(50_000), ..Limits::default() });
+  let err = engine.call("down", &[Value::Number(100_000)]).unwrap_err();
+  assert_eq!(err.kind, AsaErrorKind::CallDepthExceeded(50_000));
+}
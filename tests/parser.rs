@@ -19,16 +19,23 @@ macro_rules! test {
     }
   )
 }
+// A function expression node, whose definition is built from its parameters and body
+fn anonymous(children: &[Node]) -> Node {
+  FunctionExpression { def: std::sync::Arc::new(Interpreter::function_def(b"anonymous", children).unwrap()) }
+}
+
 // test name, test string, combinator,  expected result
 test!(parser_ident, r#"hello"#, identifier, Identifier{value: vec![104, 101, 108, 108, 111]});
 test!(parser_number, r#"123"#, number, Number{value: vec![49, 50, 51]});
+test!(parser_float, r#"1.25"#, number, Float{value: vec![49, 46, 50, 53]});
+test!(parser_decimal, r#"1.25d"#, number, Decimal{value: vec![49, 46, 50, 53]});
 test!(parser_bool, r#"true"#, boolean, Bool{value: true});
 test!(parser_string, r#""hello""#, string, String{value: vec![104, 101, 108, 108, 111]});
-test!(parser_function_call, r#"foo()"#, function_call, FunctionCall{name: vec![102, 111, 111], children: vec![
+test!(parser_function_call, r#"foo()"#, function_call, FunctionCall{name: vec![102, 111, 111], span: Span::new(1, 1, 1, 5), children: vec![
   FunctionArguments{ children: vec![
   ]}
 ]});
-test!(parser_function_call_one_arg, r#"foo(a)"#, function_call, FunctionCall{name: vec![102, 111, 111], children: vec![
+test!(parser_function_call_one_arg, r#"foo(a)"#, function_call, FunctionCall{name: vec![102, 111, 111], span: Span::new(1, 1, 1, 6), children: vec![
   FunctionArguments{ children: vec![
     Expression { children: vec![Identifier { value: vec![97] }]}
   ]}
@@ -57,7 +64,7 @@ test!(parser_variable_define_math_expr, r#"let a = 1 + 1"#, variable_define, Var
 test!(parser_variable_function_call, r#"let a = foo()"#, variable_define, VariableDefine{children: vec![
   Identifier { value: vec![97] },
   Expression { children: vec![
-    FunctionCall{name: vec![102, 111, 111], children: vec![
+    FunctionCall{name: vec![102, 111, 111], span: Span::new(1, 9, 1, 13), children: vec![
       FunctionArguments{ children: vec![
       ]}
     ]}
@@ -68,21 +75,123 @@ test!(parser_function_define, r#"fn a(){return 1;}"#, function_define, FunctionD
   children: vec![
     FunctionArguments{ children: vec![] },
     FunctionStatements{ children: vec![
-      FunctionReturn{ children: vec![ 
-        Expression { children: vec![Number{value: vec![49]}]}
+      Statement{ span: Span::new(1, 8, 1, 16), children: vec![
+        FunctionReturn{ children: vec![
+          Expression { children: vec![Number{value: vec![49]}]}
+        ]}
       ]}
     ]}
   ]
 });
+test!(parser_function_expression, r#"fn (x) { return x; }"#, function_expression, anonymous(&[
+  FunctionArguments{ children: vec![
+    ArgumentDefine{ children: vec![Identifier{value: vec![120]}] }
+  ]},
+  FunctionStatements{ children: vec![
+    Statement{ span: Span::new(1, 10, 1, 18), children: vec![
+      FunctionReturn{ children: vec![
+        Expression { children: vec![Identifier{value: vec![120]}]}
+      ]}
+    ]}
+  ]}
+]));
+test!(parser_short_function_expression, r#"|x| x * 2"#, function_expression, anonymous(&[
+  FunctionArguments{ children: vec![
+    ArgumentDefine{ children: vec![Identifier{value: vec![120]}] }
+  ]},
+  FunctionStatements{ children: vec![
+    Statement{ span: Span::new(1, 5, 1, 9), children: vec![
+      FunctionReturn{ children: vec![
+        Expression { children: vec![BinaryExpression{name: vec![42], children: vec![
+          Identifier{value: vec![120]},
+          Number{value: vec![50]},
+        ]}]}
+      ]}
+    ]}
+  ]}
+]));
+test!(parser_call_chain, r#"f()(1)"#, postfix, Call{span: Span::new(1, 1, 1, 6), children: vec![
+  FunctionCall{name: vec![102], span: Span::new(1, 1, 1, 3), children: vec![
+    FunctionArguments{ children: vec![] }
+  ]},
+  FunctionArguments{ children: vec![
+    Expression { children: vec![Number{value: vec![49]}]}
+  ]}
+]});
+test!(parser_call_index, r#"h[0](x)"#, postfix, Call{span: Span::new(1, 1, 1, 7), children: vec![
+  IndexAccess{ children: vec![
+    Identifier{value: vec![104]},
+    Expression { children: vec![Number{value: vec![48]}]}
+  ]},
+  FunctionArguments{ children: vec![
+    Expression { children: vec![Identifier{value: vec![120]}]}
+  ]}
+]});
+test!(parser_for_loop, r#"for i, x in xs { }"#, for_loop, ForLoop{ children: vec![
+  Identifier{value: vec![105]},
+  Identifier{value: vec![120]},
+  Expression { children: vec![Identifier{value: vec![120, 115]}]},
+  Block{ children: vec![]}
+]});
+test!(parser_for_range, r#"for n in 0..=9 step 3 { }"#, for_loop, ForLoop{ children: vec![
+  Null,
+  Identifier{value: vec![110]},
+  Range{ inclusive: true, children: vec![
+    Expression { children: vec![Number{value: vec![48]}]},
+    Expression { children: vec![Number{value: vec![57]}]},
+    Expression { children: vec![Number{value: vec![51]}]}
+  ]},
+  Block{ children: vec![]}
+]});
+test!(parser_compound_assignment, r#"t[i] ^= 2"#, compound_assignment, CompoundAssignment{ name: vec![94], children: vec![
+  IndexAccess{ children: vec![
+    Identifier{value: vec![116]},
+    Expression { children: vec![Identifier{value: vec![105]}]}
+  ]},
+  Expression { children: vec![Number{value: vec![50]}]}
+]});
+test!(parser_decrement, r#"n--"#, compound_assignment, CompoundAssignment{ name: vec![45], children: vec![
+  Identifier{value: vec![110]},
+  Expression { children: vec![Number{value: vec![49]}]}
+]});
+test!(parser_match_arms, r#"match v { 1 | -2 => a, [x, _] if x => { } }"#, match_expression, Match{ children: vec![
+  Expression { children: vec![Identifier{value: vec![118]}]},
+  MatchArm{ children: vec![
+    PatternAlternatives{ children: vec![
+      Number{value: vec![49]},
+      UnaryExpression{ name: vec![45], children: vec![Number{value: vec![50]}]}
+    ]},
+    Null,
+    Expression { children: vec![Identifier{value: vec![97]}]}
+  ]},
+  MatchArm{ children: vec![
+    ArrayPattern{ children: vec![Identifier{value: vec![120]}, Wildcard]},
+    Expression { children: vec![Identifier{value: vec![120]}]},
+    Block{ children: vec![]}
+  ]}
+]});
+test!(parser_range_pattern, r#"0..=9"#, pattern, Range{ inclusive: true, children: vec![
+  Number{value: vec![48]},
+  Number{value: vec![57]}
+]});
+test!(parser_map_literal, r#"{ "a": 1, b: 2, -3: c, }"#, map_literal, MapLiteral{ children: vec![
+  String{value: vec![97]},
+  Expression { children: vec![Number{value: vec![49]}]},
+  String{value: vec![98]},
+  Expression { children: vec![Number{value: vec![50]}]},
+  UnaryExpression{ name: vec![45], children: vec![Number{value: vec![51]}]},
+  Expression { children: vec![Identifier{value: vec![99]}]}
+]});
+test!(parser_empty_map_literal, r#"{}"#, map_literal, MapLiteral{ children: vec![]});
 // test!(test_ident, r#"hello"#, identifier, Identifier{value: vec![104, 101, 108, 108, 111]});
 // test!(test_number, r#"123"#, number, Number{value: vec![49, 50, 51]});
 // test!(test_bool, r#"true"#, boolean, Bool{value: true});
 // test!(test_string, r#""hello""#, string, String{value: vec![104, 101, 108, 108, 111]});
-// test!(test_function_call, r#"foo()"#, function_call, FunctionCall{name: vec![102, 111, 111], children: vec![
+// test!(test_function_call, r#"foo()"#, function_call, FunctionCall{name: vec![102, 111, 111], span: Span::new(1, 1, 1, 5), children: vec![
 //   FunctionArguments{ children: vec![
 //   ]}
 // ]});
-// test!(test_function_call_one_arg, r#"foo(a)"#, function_call, FunctionCall{name: vec![102, 111, 111], children: vec![
+// test!(test_function_call_one_arg, r#"foo(a)"#, function_call, FunctionCall{name: vec![102, 111, 111], span: Span::new(1, 1, 1, 6), children: vec![
 //   FunctionArguments{ children: vec![
 //     Expression { children: vec![Identifier { value: vec![97] }]}
 //   ]}
@@ -111,7 +220,7 @@ test!(parser_function_define, r#"fn a(){return 1;}"#, function_define, FunctionD
 // test!(test_variable_function_call, r#"let a = foo();"#, variable_define, VariableDefine{children: vec![
 //   Identifier { value: vec![97] },
 //   Expression { children: vec![
-//     FunctionCall{name: vec![102, 111, 111], children: vec![
+//     FunctionCall{name: vec![102, 111, 111], span: Span::new(1, 9, 1, 13), children: vec![
 //       FunctionArguments{ children: vec![
 //       ]}
 //     ]}
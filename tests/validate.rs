@@ -0,0 +1,41 @@
+use asa::*;
+
+macro_rules! test_validate {
+  ($func:ident, $test:tt, $expected:expr) => (
+    #[test]
+    fn $func() {
+      let tokens = lex($test);
+      let (tokens, tree) = program(tokens).unwrap();
+      assert_eq!(tokens.is_done(), true);
+      assert_eq!(validate(&tree).map_err(|e| e.kind), $expected);
+    }
+  )
+}
+
+test_validate!(validate_valid_program, r#"
+fn main() {
+    let i = 0;
+    while true {
+        i = i + 1;
+        if i > 5 { break; }
+    }
+    return i;
+}
+"#, Ok(()));
+test_validate!(validate_break_outside_loop, r#"fn main() { break; }"#, Err(AsaErrorKind::BreakOutsideLoop));
+test_validate!(validate_continue_outside_loop, r#"continue;"#, Err(AsaErrorKind::ContinueOutsideLoop));
+test_validate!(validate_return_outside_function, r#"let x = 1; return x;"#, Err(AsaErrorKind::ReturnOutsideFunction));
+test_validate!(validate_break_in_nested_if, r#"while true { if true { break; } }"#, Ok(()));
+test_validate!(validate_break_in_for_loop, r#"for x in [1] { if x > 0 { continue; } break; }"#, Ok(()));
+test_validate!(validate_break_in_match_arm, r#"while true { match 1 { 1 => { break; } _ => { continue; } } }"#, Ok(()));
+test_validate!(validate_break_in_match_value, r#"while true { let x = match 1 { _ => { break; } }; }"#, Err(AsaErrorKind::BreakOutsideLoop));
+test_validate!(validate_break_after_loop, r#"fn f() { while true { } break; }"#, Err(AsaErrorKind::BreakOutsideLoop));
+test_validate!(validate_return_in_if_expression_value, r#"let x = if true { return 1; } else { return 2; };"#, Ok(()));
+
+#[test]
+fn validate_error_points_at_statement() {
+  let (_, tree) = program(lex("fn main() {\n    let x = 1;\n    break;\n}")).unwrap();
+  let err = validate(&tree).unwrap_err();
+  assert_eq!(err.kind, AsaErrorKind::BreakOutsideLoop);
+  assert_eq!(err.span, Some(Span::new(3, 5, 3, 10)));
+}